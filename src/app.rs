@@ -1,194 +1,2372 @@
+mod arg_spec;
+mod directory_commands;
+mod du_command;
+mod file_commands;
+mod find_command;
+mod grep_command;
+mod job_commands;
+mod ls_command;
+mod rename_command;
+mod shell_commands;
+mod snapshot_commands;
+mod stat_command;
+#[cfg(test)]
+mod test_support;
+mod text_commands;
+mod tracking_commands;
+mod tree_command;
+mod watch_command;
+
+use glob::Pattern;
 use logger::{Logger, debug, error, info};
-use std::process::{Command};
+use rustyline::{
+    Config, Editor,
+    error::ReadlineError,
+    history::{DefaultHistory, History},
+};
+use std::process::{Command, ExitStatus, Stdio};
 
-use crate::helper_functions;
+use crate::{
+    app::{arg_spec::ArgSpec, job_commands::Job, ls_command::CachedListing},
+    error::MbashError,
+    file_system::{FileSystem, RealFileSystem},
+    helper_functions,
+    line_editor::MbashHelper,
+    output_sink::OutputSink,
+    parser::{self, Connector, ListSegment, OutputRedirect, PipelineStage},
+};
 use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
     env,
-    io::{self, Write},
-    path::PathBuf,
+    ffi::OsStr,
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, IsTerminal, Write},
+    mem,
+    path::{Path, PathBuf},
     sync::{
         Arc,
         atomic::{AtomicBool, Ordering},
     },
+    thread,
 };
 
 const TRACKING_FILE_PATH: &str = ".mtracking";
+const HASHES_FILE_PATH: &str = ".mhashes";
 const IGNORE_FILE_PATH: &str = ".mignoring";
+const HISTORY_FILE_PATH: &str = ".mhistory";
+const RC_FILE_NAME: &str = ".mbashrc";
+const SNAPSHOTS_DIR_PATH: &str = ".msnapshots";
+const COMMAND_NOT_FOUND_EXIT_CODE: i32 = 127;
+const INTERRUPTED_EXIT_CODE: i32 = 130;
+const HISTORY_LIMIT_ENV_VAR: &str = "MBASH_HISTORY_SIZE";
+const DEFAULT_HISTORY_LIMIT: usize = 1000;
+const PROMPT_ENV_VAR: &str = "MBASH_PROMPT";
+const PREFIX_ENV_VAR: &str = "MBASH_PREFIX";
+const PLUGIN_PREFIX: &str = "mbash-";
+const PLUGIN_CURRENT_DIR_ENV_VAR: &str = "MBASH_CURRENT_DIR";
+const TRACKING_FILE_ENV_VAR: &str = "MBASH_TRACKING_FILE";
+const IGNORE_FILE_ENV_VAR: &str = "MBASH_IGNORE_FILE";
+const DEFAULT_INTERNAL_COMMAND_PREFIX: &str = "m";
+const DEFAULT_PROMPT_TEMPLATE: &str = "mbash@ {path}: ";
+/// Shown while a command continues over several lines
+const CONTINUATION_PROMPT: &str = "> ";
+const CD_ARGS: ArgSpec = ArgSpec {
+    name: "cd",
+    flags: &[],
+    operand: "directory",
+    min_operands: 0,
+    max_operands: Some(1),
+};
+
+/// A builtin command, returning its exit status
+pub type CommandHandler = fn(&mut Mbash, &[&str]) -> i32;
+
+struct BuiltinCommand {
+    handler: CommandHandler,
+    usage: String,
+    description: String,
+}
 
 pub struct Mbash {
     exiting: Arc<AtomicBool>,
+    interrupted: Arc<AtomicBool>,
+    interactive: bool,
     current_path: PathBuf,
+    previous_path: Option<PathBuf>,
+    root_path: Option<PathBuf>,
+    dir_stack: Vec<PathBuf>,
+    tracking_file: PathBuf,
+    tracking_files: Vec<String>,
+    tracking_hashes: HashMap<String, String>,
+    tracking_changed: bool,
+    ignore_file: PathBuf,
+    ignore_patterns: Vec<String>,
+    last_exit_code: i32,
+    dry_run: bool,
+    history: Vec<String>,
+    history_limit: usize,
+    /// Where entered lines are appended, `None` when the history isn't saved
+    history_file: Option<PathBuf>,
+    aliases: HashMap<String, String>,
+    /// Variables changed by the shell, `None` hiding one inherited from the environment
+    variables: HashMap<String, Option<String>>,
+    unexported_variables: HashSet<String>,
+    exported_variables: HashSet<String>,
+    prompt_template: String,
+    input: Option<Vec<u8>>,
+    /// Where confirmation answers are read from, the terminal when `None`
+    answers: Option<Box<dyn BufRead>>,
+    output: OutputSink,
     logger: Box<dyn Logger>,
-    internal_command_prefix: &'static str,
+    file_system: Box<dyn FileSystem>,
+    internal_command_prefix: String,
     exit_command: &'static str,
+    commands: HashMap<String, BuiltinCommand>,
+    /// Whether `ls` reuses the listings of unchanged directories, off unless `set lscache on`
+    cache_listings: bool,
+    listing_cache: HashMap<PathBuf, CachedListing>,
+    jobs: Vec<Job>,
 }
 
 impl Mbash {
     pub fn new(logger: Box<dyn Logger>) -> Self {
-        Mbash {
+        Mbash::with_file_system(logger, Box::new(RealFileSystem))
+    }
+
+    /// Creates a shell that reads and writes its workspace files, and lists directories in `ls`,
+    /// through `file_system`
+    pub fn with_file_system(logger: Box<dyn Logger>, file_system: Box<dyn FileSystem>) -> Self {
+        let mut mbash = Mbash {
             exiting: Arc::new(AtomicBool::new(false)),
+            interrupted: Arc::new(AtomicBool::new(false)),
+            interactive: false,
             current_path: PathBuf::new(),
-            logger: logger,
-            internal_command_prefix: "m",
+            previous_path: None,
+            root_path: None,
+            dir_stack: Vec::new(),
+            tracking_file: PathBuf::from(TRACKING_FILE_PATH),
+            tracking_files: Vec::new(),
+            tracking_hashes: HashMap::new(),
+            tracking_changed: false,
+            ignore_file: PathBuf::from(IGNORE_FILE_PATH),
+            ignore_patterns: Vec::new(),
+            last_exit_code: 0,
+            dry_run: false,
+            history: Vec::new(),
+            history_limit: DEFAULT_HISTORY_LIMIT,
+            history_file: None,
+            aliases: HashMap::new(),
+            variables: HashMap::new(),
+            unexported_variables: HashSet::new(),
+            exported_variables: HashSet::new(),
+            prompt_template: DEFAULT_PROMPT_TEMPLATE.to_string(),
+            input: None,
+            answers: None,
+            output: OutputSink::Stdout,
+            logger,
+            file_system,
+            internal_command_prefix: DEFAULT_INTERNAL_COMMAND_PREFIX.to_string(),
             exit_command: "exit",
-        }
+            commands: HashMap::new(),
+            cache_listings: false,
+            listing_cache: HashMap::new(),
+            jobs: Vec::new(),
+        };
+
+        mbash.register_builtins();
+        mbash
+    }
+
+    /// Registers a command that can be invoked by name, replacing any command with the same name
+    pub fn register_command(&mut self, name: &str, description: &str, handler: CommandHandler) {
+        self.register_builtin(name, name, description, handler);
+    }
+
+    fn register_builtins(&mut self) {
+        self.register_builtin(
+            "cd",
+            "cd [directory|-]",
+            "Change the current directory, defaulting to the home directory. '-' returns to the previous one.",
+            Mbash::handle_cd_command,
+        );
+        self.register_builtin(
+            "pushd",
+            "pushd <directory>",
+            "Change to a directory, remembering the current one on the directory stack.",
+            Mbash::handle_pushd_command,
+        );
+        self.register_command(
+            "popd",
+            "Return to the most recently pushed directory.",
+            Mbash::handle_popd_command,
+        );
+        self.register_command(
+            "dirs",
+            "Print the directory stack, starting with the current directory.",
+            Mbash::handle_dirs_command,
+        );
+        self.register_command(
+            "pwd",
+            "Print the current directory.",
+            Mbash::handle_pwd_command,
+        );
+        self.register_builtin(
+            "ls",
+            "ls [-a|--all] [-l] [-R|--recursive] [--group-dirs] [--json] [--no-cache] [directory]",
+            "List the entries of a directory, and of every subdirectory with -R. After 'set lscache on', listings of unchanged directories are reused unless --no-cache is given.",
+            Mbash::handle_ls_command,
+        );
+        self.register_builtin(
+            "mkdir",
+            "mkdir [-p] <directory>...",
+            "Create directories, including parents with -p.",
+            Mbash::handle_mkdir_command,
+        );
+        self.register_builtin(
+            "rm",
+            "rm [-r] [-f] <path>...",
+            "Remove files, or directories with -r after confirming unless -f is given. Ignored paths are refused.",
+            Mbash::handle_rm_command,
+        );
+        self.register_builtin(
+            "touch",
+            "touch <file>...",
+            "Create files or update their modified time.",
+            Mbash::handle_touch_command,
+        );
+        self.register_builtin(
+            "cp",
+            "cp [-f] <src> <dst>",
+            "Copy a file, overwriting an existing destination only with -f.",
+            Mbash::handle_cp_command,
+        );
+        self.register_builtin(
+            "mv",
+            "mv [-f] <src> <dst>",
+            "Move or rename a path, overwriting an existing destination only with -f.",
+            Mbash::handle_mv_command,
+        );
+        self.register_builtin(
+            "rename",
+            "rename [-n] [-f] <from> <to> <file>...",
+            "Rename files by replacing the first match of a regular expression in their names. -n only shows the new names.",
+            Mbash::handle_rename_command,
+        );
+        self.register_builtin(
+            "chmod",
+            "chmod <mode> <file>...",
+            "Set the permissions of files to an octal mode like 755. Only supported on Unix.",
+            Mbash::handle_chmod_command,
+        );
+        self.register_builtin(
+            "stat",
+            "stat <path>...",
+            "Show the size, type, permissions and timestamps of paths.",
+            Mbash::handle_stat_command,
+        );
+        self.register_builtin(
+            "cat",
+            "cat [file...]",
+            "Print file contents, or piped input when no file is given.",
+            Mbash::handle_cat_command,
+        );
+        self.register_builtin(
+            "find",
+            "find [-type f|d] <pattern>",
+            "Recursively list entries under the current directory whose names match a pattern.",
+            Mbash::handle_find_command,
+        );
+        self.register_builtin(
+            "grep",
+            "grep [-i] [-n] <pattern> [file...]",
+            "Print lines matching a regular expression. -i ignores case, -n hides line numbers.",
+            Mbash::handle_grep_command,
+        );
+        self.register_builtin(
+            "tree",
+            "tree [-L depth] [directory]",
+            "Show the directory hierarchy, limited to a depth with -L.",
+            Mbash::handle_tree_command,
+        );
+        self.register_builtin(
+            "du",
+            "du [-h] [--max-depth depth] [path]",
+            "Show the total size of each subdirectory and of the whole path, skipping ignored entries.",
+            Mbash::handle_du_command,
+        );
+        self.register_builtin(
+            "wc",
+            "wc [-l] [-w] [-c] [file...]",
+            "Count the lines, words and bytes of files or piped input.",
+            Mbash::handle_wc_command,
+        );
+        self.register_builtin(
+            "head",
+            "head [-n count] [file...]",
+            "Print the first lines of files or piped input, 10 by default.",
+            Mbash::handle_head_command,
+        );
+        self.register_builtin(
+            "tail",
+            "tail [-n count] [-f] [file...]",
+            "Print the last lines of files or piped input, following appended lines with -f.",
+            Mbash::handle_tail_command,
+        );
+        self.register_builtin(
+            "echo",
+            "echo [-n] [-e] [text...]",
+            "Print the arguments separated by spaces.",
+            Mbash::handle_echo_command,
+        );
+        self.register_command(
+            "clear",
+            "Clear the terminal screen.",
+            Mbash::handle_clear_command,
+        );
+        self.register_builtin(
+            "history",
+            "history [count]",
+            "Show the command history.",
+            Mbash::handle_history_command,
+        );
+        self.register_builtin(
+            "alias",
+            "alias [name[=value]...]",
+            "Define aliases for single commands or list them.",
+            Mbash::handle_alias_command,
+        );
+        self.register_builtin(
+            "unalias",
+            "unalias <name>...",
+            "Remove aliases.",
+            Mbash::handle_unalias_command,
+        );
+        self.register_builtin(
+            "set",
+            "set [<name>=<value>...] | set prefix <prefix> | set dryrun on|off | set lscache on|off",
+            "Set session variables or list every variable, change the prefix that can be put before a command to run the mbash builtin, make rm, mv, cp, rename and restore only print what they would do, or let ls reuse the listings of unchanged directories.",
+            Mbash::handle_set_command,
+        );
+        self.register_builtin(
+            "export",
+            "export [<name>[=<value>]...]",
+            "Set variables and pass them to external commands, or list the exported ones.",
+            Mbash::handle_export_command,
+        );
+        self.register_builtin(
+            "unset",
+            "unset <name>...",
+            "Remove variables.",
+            Mbash::handle_unset_command,
+        );
+        self.register_builtin(
+            "init",
+            "init [--force]",
+            "Create the tracking files and snapshot directory in the current directory.",
+            Mbash::handle_init_command,
+        );
+        self.register_command(
+            "root",
+            "Print the root of the mbash workspace containing the current directory.",
+            Mbash::handle_root_command,
+        );
+        self.register_builtin(
+            "add",
+            "add <path|pattern>...",
+            "Start tracking files in .mtracking. Ignored files are left out, glob patterns add every matching file and '.' every untracked one.",
+            Mbash::handle_add_command,
+        );
+        self.register_command(
+            "save",
+            "Write pending tracking changes now instead of on exit.",
+            Mbash::handle_save_command,
+        );
+        self.register_builtin(
+            "untrack",
+            "untrack <path>...",
+            "Stop tracking files.",
+            Mbash::handle_untrack_command,
+        );
+        self.register_builtin(
+            "untracked",
+            "untracked [-r]",
+            "List files in the current directory that aren't tracked, descending into subdirectories with -r.",
+            Mbash::handle_untracked_command,
+        );
+        self.register_builtin(
+            "status",
+            "status [--json]",
+            "Show whether tracked files are unchanged, modified or missing since they were added.",
+            Mbash::handle_status_command,
+        );
+        self.register_command(
+            "snapshot",
+            "Save a copy of every tracked file under .msnapshots.",
+            Mbash::handle_snapshot_command,
+        );
+        self.register_builtin(
+            "restore",
+            "restore [snapshot]",
+            "Copy the files of a snapshot back to their original locations, or list the snapshots.",
+            Mbash::handle_restore_command,
+        );
+        self.register_builtin(
+            "diff",
+            "diff [path...]",
+            "Show how files differ from their latest snapshot, defaulting to every tracked file.",
+            Mbash::handle_diff_command,
+        );
+        self.register_command(
+            "watch",
+            "Report changes to tracked files as they happen until Ctrl-C is pressed.",
+            Mbash::handle_watch_command,
+        );
+        self.register_builtin(
+            "time",
+            "time <command>...",
+            "Run a command and report how long it took.",
+            Mbash::handle_time_command,
+        );
+        self.register_command(
+            "version",
+            "Print the mbash version.",
+            Mbash::handle_version_command,
+        );
+        self.register_builtin(
+            "source",
+            "source <file>",
+            "Run the lines of a file in the current session.",
+            Mbash::handle_source_command,
+        );
+        self.register_builtin(
+            ".",
+            ". <file>",
+            "Same as 'source'.",
+            Mbash::handle_source_command,
+        );
+        self.register_builtin(
+            "which",
+            "which <name>...",
+            "Show where a command comes from, either an mbash builtin or an executable in PATH.",
+            Mbash::handle_which_command,
+        );
+        self.register_builtin(
+            "type",
+            "type <name>...",
+            "Show whether a name is an alias, a builtin or an external command.",
+            Mbash::handle_type_command,
+        );
+        self.register_command(
+            "jobs",
+            "List the commands running in the background.",
+            Mbash::handle_jobs_command,
+        );
+        self.register_builtin(
+            "wait",
+            "wait [job...]",
+            "Wait until background jobs finish, defaulting to all of them.",
+            Mbash::handle_wait_command,
+        );
+        self.register_builtin(
+            "help",
+            "help [command]",
+            "List the available commands, including plugins found in PATH, or show the usage of one.",
+            Mbash::handle_help_command,
+        );
+        self.register_command("exit", "Exit mbash.", Mbash::handle_exit_command);
+    }
+
+    fn register_builtin(
+        &mut self,
+        name: &str,
+        usage: &str,
+        description: &str,
+        handler: CommandHandler,
+    ) {
+        self.commands.insert(
+            name.to_string(),
+            BuiltinCommand {
+                handler,
+                usage: usage.to_string(),
+                description: description.to_string(),
+            },
+        );
+    }
+
+    /// Loads the shell state, failing only when the current directory can't be determined
+    pub fn setup(&mut self) -> Result<(), MbashError> {
+        self.install_interrupt_handler();
+        self.set_current_dir()?;
+        self.load_settings();
+        self.load_workspace_file_names();
+        self.refresh_workspace()?;
+        self.load_history_file();
+        self.load_rc_file();
+        Ok(())
     }
 
-    pub fn setup(&mut self) {
-        self.set_current_dir();
-        self.load_file(TRACKING_FILE_PATH);
-        self.load_file(IGNORE_FILE_PATH);
+    /// Turns Ctrl-C into a flag instead of terminating mbash. The terminal delivers the signal
+    /// to a running external command directly, so only it dies, while builtins like `watch` and
+    /// the rest of a command list poll the flag to stop early.
+    /// At the prompt the line editor reads Ctrl-C as a key, which cancels the line.
+    fn install_interrupt_handler(&self) {
+        let interrupted = Arc::clone(&self.interrupted);
+        let handler_result = ctrlc::set_handler(move || interrupted.store(true, Ordering::Relaxed));
+
+        if let Err(e) = handler_result {
+            error!(self.logger, "Failed to install the Ctrl-C handler. {}", e);
+        }
     }
 
-    fn set_current_dir(&mut self) {
+    /// Refreshes `current_path` from the process working directory, logging on failure
+    fn set_current_dir(&mut self) -> Result<(), MbashError> {
         let current_dir_result = env::current_dir();
         match current_dir_result {
-            Ok(path) => self.current_path = path,
+            Ok(path) => {
+                self.current_path = path;
+                Ok(())
+            }
             Err(e) => {
                 error!(self.logger, "Failed to fetch current directory path. {}", e);
-                self.exit();
+                Err(MbashError::CurrentDir(e))
             }
         }
     }
 
     pub fn run(&mut self) {
-        while !self.exiting.load(Ordering::Relaxed) {
-            print!("mbash@ {}: ", self.current_path.display());
-
-            let flush_result = io::stdout().flush();
-            match flush_result {
-                Ok(_) => (),
-                Err(e) => {
-                    error!(self.logger, "Flush failed due to an error {}", e);
-                    continue;
-                }
+        let editor_result = Editor::with_history(Config::default(), self.editor_history());
+        let mut editor: Editor<MbashHelper, DefaultHistory> = match editor_result {
+            Ok(editor) => editor,
+            Err(e) => {
+                error!(self.logger, "Failed to initialize the line editor. {}", e);
+                return;
             }
+        };
+        editor.set_helper(Some(MbashHelper::default()));
+        self.interactive = true;
 
-            let mut input = String::new();
+        while !self.exiting.load(Ordering::Relaxed) {
+            self.interrupted.store(false, Ordering::Relaxed);
+            self.reap_jobs();
+            if let Some(helper) = editor.helper_mut() {
+                helper.update(&self.current_path, self.commands.keys().cloned().collect());
+            }
 
-            let read_result = io::stdin().read_line(&mut input);
-            match read_result {
-                Ok(_) => {
-                    let command_line = input.trim();
+            match editor.readline(&self.render_prompt()) {
+                Ok(input) => {
+                    let Some(command_line) = self.read_continuation_lines(&mut editor, input)
+                    else {
+                        debug!(self.logger, "Input line cancelled.");
+                        continue;
+                    };
+                    let command_line = command_line.trim();
                     if command_line.is_empty() {
                         debug!(self.logger, "User input is empty.");
                         continue;
                     }
 
-                    let parts: Vec<&str> = command_line.split_whitespace().collect();
-                    if parts.is_empty() {
-                        debug!(
-                            self.logger,
-                            "Splitting the input using whitespaces resulted in an empty vector."
-                        );
-                        return;
+                    self.handle_input(command_line);
+                    if let Some(entry) = self.history.last() {
+                        let _ = editor.add_history_entry(entry.as_str());
                     }
+                }
+                Err(ReadlineError::Interrupted) => {
+                    debug!(self.logger, "Input line cancelled.");
+                    continue;
+                }
+                Err(ReadlineError::Eof) => {
+                    info!(self.logger, "Reached end of input, exiting.");
+                    self.exit();
+                }
+                Err(e) => {
+                    error!(
+                        self.logger,
+                        "Failed to read user input due to an error '{}'.", e
+                    );
+                    self.exit();
+                }
+            }
+        }
+    }
+
+    /// Builds the history the up and down arrows walk through, starting from the loaded entries
+    fn editor_history(&self) -> DefaultHistory {
+        let mut history = DefaultHistory::new();
+        for entry in &self.history {
+            let _ = history.add(entry);
+        }
+        history
+    }
+
+    /// Keeps reading lines with the continuation prompt while the command line is incomplete.
+    /// Returns `None` when Ctrl-C cancels it; on end of input the incomplete line is returned as is.
+    fn read_continuation_lines(
+        &self,
+        editor: &mut Editor<MbashHelper, DefaultHistory>,
+        mut command_line: String,
+    ) -> Option<String> {
+        while let Some(continuation) = parser::continuation(command_line.trim_start()) {
+            match editor.readline(CONTINUATION_PROMPT) {
+                Ok(line) => parser::continue_line(&mut command_line, continuation, &line),
+                Err(ReadlineError::Interrupted) => return None,
+                Err(e) => {
+                    debug!(self.logger, "Stopped reading continuation lines: '{}'.", e);
+                    break;
+                }
+            }
+        }
+        Some(command_line)
+    }
+
+    /// Replaces the destination of builtin output and returns the previous one.
+    /// Installing an `OutputSink::Buffer` captures output in memory until it is swapped back.
+    pub fn set_output(&mut self, output: OutputSink) -> OutputSink {
+        mem::replace(&mut self.output, output)
+    }
+
+    /// Changes the word that marks a command as internal, as in `m ls`. Empty prefixes are
+    /// refused and a prefix shadowing a builtin is accepted with a warning.
+    pub fn set_internal_command_prefix(&mut self, prefix: &str) -> bool {
+        if prefix.is_empty() || prefix.contains(char::is_whitespace) {
+            error!(
+                self.logger,
+                "The command prefix must be a single non-empty word, got '{}'.", prefix
+            );
+            return false;
+        }
+
+        if self.commands.contains_key(prefix) {
+            info!(
+                self.logger,
+                "The command prefix '{}' shadows the builtin with the same name.", prefix
+            );
+        }
 
-                    let first_word = parts[0];
-                    if first_word == self.internal_command_prefix {
-                        debug!(self.logger, "Received an internal command.");
+        debug!(self.logger, "Set the command prefix to '{}'.", prefix);
+        self.internal_command_prefix = prefix.to_string();
+        true
+    }
+
+    /// Returns the exit code of the most recently executed command
+    pub fn last_exit_code(&self) -> i32 {
+        self.last_exit_code
+    }
+
+    /// Runs every line of a script through the shell, skipping blank lines and `#` comments
+    pub fn run_script(&mut self, path: &Path) -> io::Result<()> {
+        let contents = fs::read_to_string(path)?;
+        debug!(self.logger, "Running script '{}'.", path.display());
+
+        let mut command_line = String::new();
+        for line in contents.lines() {
+            if self.exiting.load(Ordering::Relaxed) || self.interrupted.load(Ordering::Relaxed) {
+                break;
+            }
+
+            match parser::continuation(&command_line) {
+                Some(continuation) => parser::continue_line(&mut command_line, continuation, line),
+                None => {
+                    let line = line.trim();
+                    if line.is_empty() || line.starts_with('#') {
                         continue;
                     }
+                    command_line = line.to_string();
+                }
+            }
 
-                    let command_name = parts[0];
-                    let args = &parts[1..];
+            if parser::continuation(&command_line).is_none() {
+                self.execute_line(command_line.trim());
+                command_line.clear();
+            }
+        }
 
-                    if command_line == self.exit_command {
-                        self.exit();
-                        info!(
-                            self.logger,
-                            "Received '{}' command, exiting mbash.", self.exit_command
-                        );
-                        break;
-                    }
+        // An incomplete last command still runs, so its parse error is reported
+        if !command_line.is_empty() {
+            self.execute_line(command_line.trim());
+        }
+
+        Ok(())
+    }
+
+    /// Fills in the `{path}`, `{user}` and `{exit}` placeholders of the prompt template
+    fn render_prompt(&self) -> String {
+        let user = env::var("USER")
+            .or_else(|_| env::var("USERNAME"))
+            .unwrap_or_default();
+
+        self.prompt_template
+            .replace("{path}", &self.current_path.display().to_string())
+            .replace("{user}", &user)
+            .replace("{exit}", &self.last_exit_code.to_string())
+    }
+
+    pub fn handle_input(&mut self, command_line: &str) {
+        let command_line = match self.expand_history(command_line) {
+            Some(expanded) => expanded,
+            None => {
+                self.last_exit_code = 1;
+                return;
+            }
+        };
+        let command_line = command_line.as_str();
+        self.record_history(command_line);
+        self.execute_line(command_line);
+    }
+
+    /// Runs a command line like [`Mbash::execute_line`], turning a non-zero status into
+    /// [`MbashError::CommandFailed`]
+    pub fn try_execute_line(&mut self, command_line: &str) -> Result<(), MbashError> {
+        self.execute_line(command_line);
+        match self.last_exit_code {
+            0 => Ok(()),
+            status => Err(MbashError::CommandFailed {
+                command: command_line.to_string(),
+                status,
+            }),
+        }
+    }
+
+    /// Runs a command line without touching the history, for driving the shell without stdin
+    pub fn execute_line(&mut self, command_line: &str) {
+        let segments = match parser::split_command_list(command_line) {
+            Ok(segments) => segments,
+            Err(e) => {
+                error!(self.logger, "Failed to parse '{}': {}.", command_line, e);
+                self.last_exit_code = 1;
+                return;
+            }
+        };
+
+        for ListSegment {
+            connector,
+            text: segment,
+            background,
+        } in segments
+        {
+            if self.exiting.load(Ordering::Relaxed) {
+                break;
+            }
+
+            if self.interrupted.load(Ordering::Relaxed) {
+                info!(
+                    self.logger,
+                    "Interrupted, skipping the rest of '{}'.", command_line
+                );
+                self.last_exit_code = INTERRUPTED_EXIT_CODE;
+                break;
+            }
+
+            let should_run = match connector {
+                Connector::Always => true,
+                Connector::IfSuccess => self.last_exit_code == 0,
+                Connector::IfFailure => self.last_exit_code != 0,
+            };
+
+            if should_run && background {
+                self.execute_in_background(&segment);
+            } else if should_run {
+                self.execute_segment(&segment);
+            } else {
+                debug!(
+                    self.logger,
+                    "Skipping '{}' because of the '{}' operator.",
+                    segment.trim(),
+                    connector
+                );
+            }
+        }
+    }
+
+    fn execute_segment(&mut self, segment: &str) {
+        let Some(stages) = self.parse_segment(segment) else {
+            return;
+        };
+
+        if stages.is_empty() {
+            debug!(
+                self.logger,
+                "Tokenizing the input resulted in an empty vector."
+            );
+            return;
+        }
+
+        self.execute_pipeline(stages);
+    }
+
+    /// Expands and parses a segment into pipeline stages, setting a failing exit code when it's invalid
+    fn parse_segment(&mut self, segment: &str) -> Option<Vec<PipelineStage>> {
+        let parse_result = parser::expand_braces(segment)
+            .and_then(|expanded| {
+                parser::tokenize(
+                    &expanded,
+                    self.last_exit_code,
+                    &|name| self.variable(name),
+                    &self.current_path,
+                )
+            })
+            .and_then(parser::parse_pipeline);
+
+        match parse_result {
+            Ok(stages) => Some(stages),
+            Err(e) => {
+                error!(self.logger, "Failed to parse '{}': {}.", segment, e);
+                self.last_exit_code = 1;
+                None
+            }
+        }
+    }
+
+    /// Runs each stage with the previous stage's output as its input, the last stage
+    /// writing to the current output sink unless it's redirected to a file
+    fn execute_pipeline(&mut self, stages: Vec<PipelineStage>) {
+        let last_stage_index = stages.len() - 1;
+        let mut stage_input = None;
 
-                    self.execute_external_command(command_name, args);
+        for (index, stage) in stages.into_iter().enumerate() {
+            self.input = stage_input.take();
+
+            if let Some(input_path) = &stage.input_redirect {
+                match self.read_input_redirect(input_path) {
+                    Some(input) => self.input = Some(input),
+                    None => {
+                        self.last_exit_code = 1;
+                        self.input = None;
+                        return;
+                    }
                 }
-                Err(e) => {
-                    error!(
-                        self.logger,
-                        "Failed to read user input due to an error '{}'.", e
-                    );
+            }
 
-                    continue;
+            let stage_output = match &stage.output_redirect {
+                Some(redirect) => match self.open_output_redirect(redirect) {
+                    Some(file) => Some(OutputSink::File(file)),
+                    None => {
+                        self.last_exit_code = 1;
+                        self.input = None;
+                        return;
+                    }
+                },
+                None if index != last_stage_index => Some(OutputSink::Buffer(Vec::new())),
+                None => None,
+            };
+
+            match stage_output {
+                Some(stage_output) => {
+                    let previous_output = mem::replace(&mut self.output, stage_output);
+                    self.execute_command(stage.words);
+                    let captured_output = mem::replace(&mut self.output, previous_output);
+                    stage_input = Some(captured_output.into_buffer());
                 }
+                None => self.execute_command(stage.words),
+            }
+
+            self.input = None;
+        }
+    }
+
+    fn read_input_redirect(&self, input_path: &str) -> Option<Vec<u8>> {
+        let path = self.resolve_path(input_path);
+        match fs::read(&path) {
+            Ok(input) => Some(input),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                error!(
+                    self.logger,
+                    "Can't redirect input from '{}', no such file.",
+                    path.display()
+                );
+                None
+            }
+            Err(e) => {
+                error!(
+                    self.logger,
+                    "Failed to read '{}' for input redirection: '{}'.",
+                    path.display(),
+                    e
+                );
+                None
+            }
+        }
+    }
+
+    fn open_output_redirect(&self, redirect: &OutputRedirect) -> Option<File> {
+        let path = self.resolve_path(&redirect.path);
+        let open_result = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(redirect.append)
+            .truncate(!redirect.append)
+            .open(&path);
+
+        match open_result {
+            Ok(file) => Some(file),
+            Err(e) => {
+                error!(
+                    self.logger,
+                    "Failed to open '{}' for writing: '{}'.",
+                    path.display(),
+                    e
+                );
+                None
             }
         }
     }
 
-    fn execute_external_command(&mut self, command_name: &str, args: &[&str]) {
-        if command_name.starts_with("cd") {
-            self.handle_cd_command(args);
+    fn execute_command(&mut self, words: Vec<String>) {
+        let mut command_name_index = 0;
+        if words[0] == self.internal_command_prefix {
+            debug!(self.logger, "Received an internal command.");
+            command_name_index = 1;
+        }
+
+        if command_name_index >= words.len() {
+            debug!(
+                self.logger,
+                "No command supplied after prefix '{}'.", self.internal_command_prefix
+            );
+            return;
+        }
+
+        let words = self.expand_aliases(words.into_iter().skip(command_name_index).collect());
+        let parts: Vec<&str> = words.iter().map(String::as_str).collect();
+        let Some((&command_name, args)) = parts.split_first() else {
+            debug!(self.logger, "Alias expansion resulted in an empty command.");
+            return;
+        };
+
+        if let Some(command) = self.commands.get(command_name) {
+            let handler = command.handler;
+            self.last_exit_code = handler(self, args);
+            return;
+        }
+
+        if let Some(plugin_path) = self.find_plugin(command_name) {
+            self.last_exit_code = self.execute_plugin_command(&plugin_path, args);
             return;
         }
 
+        self.last_exit_code = self.execute_external_command(command_name, args);
+    }
+
+    /// Runs a program from `PATH`, returning its exit status
+    fn execute_external_command(&mut self, command_name: &str, args: &[&str]) -> i32 {
+        self.run_program(self.external_command(command_name), command_name, args)
+    }
+
+    /// Runs `mbash-<name>` from `PATH` for a command mbash doesn't know, the way git runs
+    /// `git-<name>`
+    fn execute_plugin_command(&mut self, plugin_path: &Path, args: &[&str]) -> i32 {
+        let command = self.plugin_command(plugin_path);
+        self.run_program(command, &plugin_path.to_string_lossy(), args)
+    }
+
+    /// Prepares `program` to run in the current directory with the exported variables
+    fn external_command(&self, program: impl AsRef<OsStr>) -> Command {
+        let mut command = Command::new(program);
+        command.current_dir(&self.current_path);
+        for (name, value) in &self.variables {
+            match value {
+                Some(value) if !self.unexported_variables.contains(name) => {
+                    command.env(name, value);
+                }
+                _ => {
+                    command.env_remove(name);
+                }
+            }
+        }
+        command
+    }
+
+    /// Prepares a plugin, which can read the current directory from `MBASH_CURRENT_DIR`
+    fn plugin_command(&self, plugin_path: &Path) -> Command {
+        let mut command = self.external_command(plugin_path);
+        command.env(PLUGIN_CURRENT_DIR_ENV_VAR, &self.current_path);
+        command
+    }
+
+    /// Runs `command` in the current directory with the pipeline's input and output
+    fn run_program(&mut self, mut command: Command, command_name: &str, args: &[&str]) -> i32 {
         debug!(self.logger, "{}", command_name);
         for arg in args {
             debug!(self.logger, "{}", arg);
         }
 
-        let mut command = Command::new(command_name);
-        command.args(args);
+        command.args(args).stderr(Stdio::inherit());
+
+        if self.input.is_some() {
+            command.stdin(Stdio::piped());
+        } else {
+            command.stdin(Stdio::inherit());
+        }
+
+        if self.output.is_stdout() {
+            command.stdout(Stdio::inherit());
+        } else {
+            command.stdout(Stdio::piped());
+        }
+
+        let mut child = match command.spawn() {
+            Ok(child) => child,
+            Err(e) => {
+                error!(
+                    self.logger,
+                    "Failed to execute command '{}': {}", command_name, e
+                );
+                return COMMAND_NOT_FOUND_EXIT_CODE;
+            }
+        };
+
+        // Feeding stdin from another thread keeps a child that fills its stdout pipe
+        // before reading all of its input from deadlocking
+        let input_writer = match (self.input.take(), child.stdin.take()) {
+            (Some(input), Some(mut stdin)) => Some(thread::spawn(move || stdin.write_all(&input))),
+            _ => None,
+        };
+
+        let output_result = child.wait_with_output();
+        if let Some(Ok(Err(e))) = input_writer.map(|writer| writer.join()) {
+            debug!(
+                self.logger,
+                "Failed to write input to '{}': {}", command_name, e
+            );
+        }
+
+        match output_result {
+            Ok(output) => {
+                let _ = self.output.write_all(&output.stdout);
 
-        match command.status() {
-            Ok(status) => {
-                if !status.success() {
+                if !output.status.success() {
                     error!(
                         self.logger,
-                        "Command '{}' failed with status: {}", command_name, status
+                        "Command '{}' failed with status: {}", command_name, output.status
                     );
-                    return;
+                    return exit_status_code(output.status);
                 }
 
                 debug!(
                     self.logger,
-                    "Command '{} suceeeded with status '{}'.", command_name, status
+                    "Command '{}' succeeded with status '{}'.", command_name, output.status
                 );
+                0
             }
             Err(e) => {
                 error!(
                     self.logger,
-                    "Failed to execute command '{}': {}", command_name, e
+                    "Failed to wait for command '{}': {}", command_name, e
                 );
+                1
             }
         }
     }
 
-    fn handle_cd_command(&mut self, args: &[&str]) {
-        let new_dir = &args[0];
+    fn handle_cd_command(&mut self, args: &[&str]) -> i32 {
+        let Some(args) = self.parse_args(&CD_ARGS, args) else {
+            return 1;
+        };
 
-        if new_dir.is_empty() {
-            debug!(
-                self.logger,
-                "'cd' command requires a directory as an argument [cd <directory>]."
-            );
-            return;
-        }
+        let new_dir = match args.operands.first() {
+            Some(&"-") => {
+                let Some(previous_path) = self.previous_path.clone() else {
+                    error!(
+                        self.logger,
+                        "'cd -' used but there is no previous directory."
+                    );
+                    return 1;
+                };
+
+                let status = self.change_directory(&previous_path.to_string_lossy());
+                if status == 0 {
+                    let _ = writeln!(self.output, "{}", self.current_path.display());
+                }
+                return status;
+            }
+            Some(dir) => dir.to_string(),
+            None => match helper_functions::home_dir() {
+                Some(home) => {
+                    debug!(
+                        self.logger,
+                        "No directory supplied to 'cd', falling back to home directory '{}'.", home
+                    );
+                    home
+                }
+                None => {
+                    error!(
+                        self.logger,
+                        "Couldn't determine the home directory, 'cd' requires a directory as an argument [cd <directory>]."
+                    );
+                    return 1;
+                }
+            },
+        };
+
+        self.change_directory(&new_dir)
+    }
+
+    /// Moves both the process and `current_path` to `target`, resolved against the current path.
+    /// The directory being left is remembered for `cd -`.
+    fn change_directory(&mut self, target: &str) -> i32 {
+        let path = self.resolve_path(target);
+        let validation_result = fs::metadata(&path).and_then(|metadata| {
+            if metadata.is_dir() {
+                Ok(())
+            } else {
+                Err(io::Error::from(io::ErrorKind::NotADirectory))
+            }
+        });
 
-        match env::set_current_dir(new_dir) {
+        match validation_result.and_then(|_| env::set_current_dir(&path)) {
             Ok(()) => {
-                debug!(self.logger, "Changed directory to '{}'.", new_dir);
-                self.set_current_dir();
+                debug!(self.logger, "Changed directory to '{}'.", target);
+                self.previous_path = Some(self.current_path.clone());
+                let status = if self.set_current_dir().is_ok() {
+                    0
+                } else {
+                    // The change itself succeeded, so keep `current_path` in step with the process
+                    self.current_path = helper_functions::normalize_path(&path);
+                    1
+                };
+                if let Err(e) = self.refresh_workspace() {
+                    error!(self.logger, "{}", e);
+                }
+                status
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                error!(
+                    self.logger,
+                    "Failed to change directory to '{}': no such file or directory.", target
+                );
+                1
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotADirectory => {
+                error!(
+                    self.logger,
+                    "Failed to change directory to '{}': not a directory.", target
+                );
+                1
+            }
+            Err(e) if e.kind() == io::ErrorKind::PermissionDenied => {
+                error!(
+                    self.logger,
+                    "Failed to change directory to '{}': permission denied.", target
+                );
+                1
             }
             Err(e) => {
                 error!(
                     self.logger,
-                    "Failed to change directory to '{}': '{}'.", new_dir, e
+                    "Failed to change directory to '{}': '{}'.", target, e
                 );
+                1
             }
         }
     }
 
-    pub fn exit(&self) {
-        if self.exiting.load(Ordering::Relaxed) {
-            return;
+    fn handle_pwd_command(&mut self, args: &[&str]) -> i32 {
+        if !args.is_empty() {
+            debug!(
+                self.logger,
+                "'pwd' doesn't take any arguments, ignoring {:?}.", args
+            );
+        }
+
+        let _ = writeln!(self.output, "{}", self.current_path.display());
+        0
+    }
+
+    /// Moves to the closest ancestor of `current_path` that still exists, or to the home
+    /// directory when none does. Returns whether a valid directory was reached.
+    fn recover_current_path(&mut self) -> bool {
+        let fallback = self
+            .current_path
+            .ancestors()
+            .skip(1)
+            .find(|ancestor| ancestor.is_dir())
+            .map(Path::to_path_buf)
+            .or_else(|| helper_functions::home_dir().map(PathBuf::from));
+
+        let Some(fallback) = fallback else {
+            error!(
+                self.logger,
+                "Couldn't find an existing directory to fall back to."
+            );
+            return false;
+        };
+
+        info!(self.logger, "Falling back to '{}'.", fallback.display());
+        self.change_directory(&fallback.to_string_lossy()) == 0
+    }
+
+    fn resolve_path(&self, path: &str) -> PathBuf {
+        self.current_path.join(path)
+    }
+
+    /// In dry-run mode, prints the action a destructive command was about to take and returns
+    /// `true` so the command skips it
+    fn skip_for_dry_run(&mut self, action: &str) -> bool {
+        if !self.dry_run {
+            return false;
         }
 
-        self.exiting.load(Ordering::Relaxed);
+        let _ = writeln!(self.output, "dry run: would {}", action);
+        true
     }
 
-    fn load_file(&self, file_name: &str) {
-        helper_functions::attempt_create_file(file_name);
-        // todo
+    /// Finds the `mbash-<name>` executable that runs `name` as a plugin command
+    fn find_plugin(&self, name: &str) -> Option<PathBuf> {
+        if name.contains(['/', '\\']) {
+            return None;
+        }
+        helper_functions::find_executable(&format!("{}{}", PLUGIN_PREFIX, name))
+    }
+
+    /// Asks a yes or no question on the terminal before a destructive action, defaulting to no.
+    /// Scripts, `-c` commands and piped input are never asked and always get no.
+    fn confirm(&mut self, question: &str) -> bool {
+        if !self.interactive || (self.answers.is_none() && !io::stdin().is_terminal()) {
+            debug!(
+                self.logger,
+                "Answering no to '{}' since mbash isn't interactive.", question
+            );
+            return false;
+        }
+
+        print!("{} [y/N] ", question);
+        let _ = io::stdout().flush();
+
+        match self.answers.take() {
+            Some(mut answers) => {
+                let answer = self.read_answer(&mut answers);
+                self.answers = Some(answers);
+                answer
+            }
+            None => self.read_answer(&mut io::stdin().lock()),
+        }
+    }
+
+    /// Reads the answer to a [`Mbash::confirm`] question, where only `y` and `yes` mean yes
+    fn read_answer(&self, input: &mut impl BufRead) -> bool {
+        let mut answer = String::new();
+        if let Err(e) = input.read_line(&mut answer) {
+            error!(self.logger, "Failed to read the answer: '{}'.", e);
+            return false;
+        }
+        matches!(answer.trim().to_ascii_lowercase().as_str(), "y" | "yes")
+    }
+
+    /// Checks whether a path matches one of the loaded ignore patterns.
+    /// Patterns ending in `/` match the directory itself and anything under it.
+    fn is_ignored(&self, path: &str) -> bool {
+        // Patterns are written relative to the workspace root, while callers pass paths relative
+        // to the current path
+        let absolute_path = helper_functions::normalize_path(&self.resolve_path(path));
+        let path = match &self.root_path {
+            Some(root_path) => absolute_path
+                .strip_prefix(root_path)
+                .unwrap_or(Path::new(path)),
+            None => Path::new(path),
+        };
+        let file_name = path.file_name().and_then(|name| name.to_str());
+
+        self.ignore_patterns.iter().any(|raw_pattern| {
+            let (raw_pattern, directory_only) = match raw_pattern.strip_suffix('/') {
+                Some(directory) => (directory, true),
+                None => (raw_pattern.as_str(), false),
+            };
+
+            let pattern = match Pattern::new(raw_pattern) {
+                Ok(pattern) => pattern,
+                Err(e) => {
+                    debug!(
+                        self.logger,
+                        "'{}' isn't a valid glob pattern, matching it literally. {}",
+                        raw_pattern,
+                        e
+                    );
+                    return path == Path::new(raw_pattern) || file_name == Some(raw_pattern);
+                }
+            };
+
+            if directory_only {
+                return path
+                    .components()
+                    .filter_map(|component| component.as_os_str().to_str())
+                    .any(|component| pattern.matches(component));
+            }
+
+            pattern.matches_path(path) || file_name.is_some_and(|name| pattern.matches(name))
+        })
+    }
+
+    fn handle_exit_command(&mut self, _args: &[&str]) -> i32 {
+        self.exit();
+        info!(
+            self.logger,
+            "Received '{}' command, exiting mbash.", self.exit_command
+        );
+        0
+    }
+
+    pub fn exit(&self) {
+        if self.exiting.load(Ordering::Relaxed) {
+            return;
+        }
+
+        self.exiting.store(true, Ordering::Relaxed);
+    }
+
+    /// Reads the history size, prompt and command prefix from the environment
+    fn load_settings(&mut self) {
+        if let Some(limit) = env::var(HISTORY_LIMIT_ENV_VAR)
+            .ok()
+            .and_then(|limit| limit.parse().ok())
+        {
+            self.history_limit = limit;
+        }
+        if let Ok(prompt_template) = env::var(PROMPT_ENV_VAR) {
+            self.prompt_template = prompt_template;
+        }
+        if let Ok(prefix) = env::var(PREFIX_ENV_VAR) {
+            self.set_internal_command_prefix(&prefix);
+        }
+    }
+
+    /// Lets `MBASH_TRACKING_FILE` and `MBASH_IGNORE_FILE` replace the default workspace file names
+    fn load_workspace_file_names(&mut self) {
+        if let Ok(tracking_file) = env::var(TRACKING_FILE_ENV_VAR) {
+            debug!(
+                self.logger,
+                "Using '{}' as the tracking file.", tracking_file
+            );
+            self.tracking_file = PathBuf::from(tracking_file);
+        }
+        if let Ok(ignore_file) = env::var(IGNORE_FILE_ENV_VAR) {
+            debug!(self.logger, "Using '{}' as the ignore file.", ignore_file);
+            self.ignore_file = PathBuf::from(ignore_file);
+        }
+    }
+
+    /// Finds the closest directory at or above `current_path` holding a tracking or ignore file,
+    /// the way git looks for `.git`, and reloads the workspace files when it changes
+    fn refresh_workspace(&mut self) -> Result<(), MbashError> {
+        let root_path = self
+            .current_path
+            .ancestors()
+            .find(|dir| {
+                self.is_workspace_file(&dir.join(&self.tracking_file))
+                    || self.is_workspace_file(&dir.join(&self.ignore_file))
+            })
+            .map(Path::to_path_buf);
+        if root_path == self.root_path {
+            return Ok(());
+        }
+
+        self.save_tracking_changes();
+        match &root_path {
+            Some(root_path) => debug!(
+                self.logger,
+                "Using the workspace at '{}'.",
+                root_path.display()
+            ),
+            None => debug!(self.logger, "Not inside an mbash workspace anymore."),
+        }
+        self.root_path = root_path;
+        self.load_hashes_file();
+        self.load_ignore_file();
+        self.load_tracking_file()
+    }
+
+    fn is_workspace_file(&self, path: &Path) -> bool {
+        self.file_system
+            .metadata(path)
+            .is_ok_and(|metadata| metadata.is_file())
+    }
+
+    /// Resolves a workspace file such as `.mtracking` against the workspace root, or against the
+    /// current path outside of a workspace
+    fn workspace_file(&self, file_name: &Path) -> PathBuf {
+        self.root_path
+            .as_deref()
+            .unwrap_or(&self.current_path)
+            .join(file_name)
+    }
+
+    /// Loads the tracked paths, normalizing relative ones against the workspace root and dropping duplicates
+    fn load_tracking_file(&mut self) -> Result<(), MbashError> {
+        let path = self.workspace_file(&self.tracking_file);
+        let entries = self
+            .read_entries(&path.to_string_lossy())
+            .map_err(|source| MbashError::TrackingLoad { path, source })?;
+
+        self.tracking_files.clear();
+        for entry in entries {
+            let path = self.resolve_workspace_entry(&entry);
+
+            if self.tracking_files.contains(&path) {
+                debug!(self.logger, "Dropping duplicate tracked path '{}'.", entry);
+                continue;
+            }
+            self.tracking_files.push(path);
+        }
+        Ok(())
+    }
+
+    /// Turns a path stored in a workspace file into the normalized absolute path kept in memory
+    fn resolve_workspace_entry(&self, entry: &str) -> String {
+        let path = helper_functions::normalize_path(&self.workspace_file(Path::new(entry)));
+        path.to_string_lossy().into_owned()
+    }
+
+    /// Stores a tracked path relative to the workspace root, so the workspace can be moved.
+    /// Paths outside of the root stay absolute.
+    fn workspace_entry<'a>(&self, path: &'a str) -> Cow<'a, str> {
+        let root = self.workspace_file(Path::new(""));
+        match Path::new(path).strip_prefix(&root) {
+            Ok(relative) => Cow::Owned(relative.to_string_lossy().into_owned()),
+            Err(_) => Cow::Borrowed(path),
+        }
+    }
+
+    /// Reads `<hash>  <path>` lines, the same layout `sha256sum` produces
+    fn load_hashes_file(&mut self) {
+        let hashes_file = self.workspace_file(Path::new(HASHES_FILE_PATH));
+        if let Some(entries) = self.load_entries(&hashes_file.to_string_lossy()) {
+            self.tracking_hashes = entries
+                .iter()
+                .filter_map(|entry| entry.split_once("  "))
+                .map(|(hash, path)| (self.resolve_workspace_entry(path), hash.to_string()))
+                .collect();
+        }
+    }
+
+    fn load_ignore_file(&mut self) {
+        let ignore_file = self.workspace_file(&self.ignore_file);
+        if let Some(entries) = self.load_entries(&ignore_file.to_string_lossy()) {
+            self.ignore_patterns = entries;
+        }
+    }
+
+    /// Reads the non-empty, trimmed lines of a file, logging read errors
+    fn load_entries(&self, file_name: &str) -> Option<Vec<String>> {
+        match self.read_entries(file_name) {
+            Ok(entries) => Some(entries),
+            Err(e) => {
+                error!(self.logger, "Failed to read '{}' file. {}", file_name, e);
+                None
+            }
+        }
+    }
+
+    /// Reads the non-empty, trimmed lines of a file, treating a missing file as empty
+    fn read_entries(&self, file_name: &str) -> io::Result<Vec<String>> {
+        match self.file_system.read_to_string(Path::new(file_name)) {
+            Ok(contents) => {
+                let entries: Vec<String> = contents
+                    .lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty())
+                    .map(String::from)
+                    .collect();
+
+                debug!(
+                    self.logger,
+                    "Loaded {} entries from '{}'.",
+                    entries.len(),
+                    file_name
+                );
+                Ok(entries)
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                debug!(self.logger, "'{}' doesn't exist yet.", file_name);
+                Ok(Vec::new())
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Loads `~/.mhistory`, so every session shares one history wherever it starts. Without a
+    /// home directory the history only lasts for the session.
+    fn load_history_file(&mut self) {
+        let Some(home) = helper_functions::home_dir() else {
+            debug!(
+                self.logger,
+                "Couldn't determine the home directory, the history won't be saved."
+            );
+            self.history_file = None;
+            self.history.clear();
+            return;
+        };
+        let history_file = Path::new(&home).join(HISTORY_FILE_PATH);
+
+        match self.load_entries(&history_file.to_string_lossy()) {
+            Some(entries) => {
+                let mut entries: Vec<String> = entries
+                    .iter()
+                    .map(|entry| unescape_history_entry(entry))
+                    .collect();
+                let overflow = entries.len().saturating_sub(self.history_limit);
+                entries.drain(..overflow);
+                self.history = entries;
+            }
+            None => {
+                error!(
+                    self.logger,
+                    "Couldn't load '{}', starting with an empty history.",
+                    history_file.display()
+                );
+                self.history.clear();
+            }
+        }
+        self.history_file = Some(history_file);
+    }
+
+    /// Runs `~/.mbashrc` like a script so users can predefine aliases, the prompt and tracked files
+    fn load_rc_file(&mut self) {
+        let Some(home) = helper_functions::home_dir() else {
+            debug!(
+                self.logger,
+                "Couldn't determine the home directory, skipping '{}'.", RC_FILE_NAME
+            );
+            return;
+        };
+
+        let rc_path = Path::new(&home).join(RC_FILE_NAME);
+        if !rc_path.is_file() {
+            debug!(
+                self.logger,
+                "No '{}' found, skipping it.",
+                rc_path.display()
+            );
+            return;
+        }
+
+        if let Err(e) = self.run_script(&rc_path) {
+            error!(
+                self.logger,
+                "Failed to load '{}': '{}'.",
+                rc_path.display(),
+                e
+            );
+        }
+    }
+
+    /// Writes the tracked paths and their hashes if they changed since they were last loaded or
+    /// saved. `add` and `untrack` only update memory, so tracking many files costs one write.
+    pub fn save_tracking_changes(&mut self) -> bool {
+        if !self.tracking_changed {
+            return true;
+        }
+
+        let saved = self.save_tracking_file() && self.save_hashes_file();
+        if saved {
+            debug!(self.logger, "Saved the tracking changes.");
+            self.tracking_changed = false;
+        }
+        saved
+    }
+
+    fn save_tracking_file(&self) -> bool {
+        let contents: String = self
+            .tracking_files
+            .iter()
+            .map(|path| format!("{}\n", self.workspace_entry(path)))
+            .collect();
+
+        let tracking_file = self.workspace_file(&self.tracking_file);
+        if let Err(e) = self.write_file(&tracking_file, &contents) {
+            error!(
+                self.logger,
+                "Failed to write '{}' file. {}",
+                tracking_file.display(),
+                e
+            );
+            return false;
+        }
+        true
+    }
+
+    fn save_hashes_file(&self) -> bool {
+        let contents: String = self
+            .tracking_files
+            .iter()
+            .filter_map(|path| {
+                let hash = self.tracking_hashes.get(path)?;
+                Some(format!("{}  {}\n", hash, self.workspace_entry(path)))
+            })
+            .collect();
+
+        let hashes_file = self.workspace_file(Path::new(HASHES_FILE_PATH));
+        if let Err(e) = self.write_file(&hashes_file, &contents) {
+            error!(
+                self.logger,
+                "Failed to write '{}' file. {}",
+                hashes_file.display(),
+                e
+            );
+            return false;
+        }
+        true
+    }
+
+    /// Replaces a file's contents through the shell's file system
+    fn write_file(&self, path: &Path, contents: &str) -> io::Result<()> {
+        self.file_system
+            .create(path)?
+            .write_all(contents.as_bytes())
+    }
+}
+
+/// Maps a child's exit status to a shell exit code, using `128 + signal` for signalled children
+fn exit_status_code(status: ExitStatus) -> i32 {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+
+        if let Some(signal) = status.signal() {
+            return 128 + signal;
+        }
+    }
+
+    status.code().unwrap_or(1)
+}
+
+/// Turns a history entry into a single line of the history file. Lines continued inside quotes
+/// keep their newlines, written as `\n` with backslashes doubled so they read back unchanged.
+fn escape_history_entry(entry: &str) -> String {
+    entry.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+/// Reverses [`escape_history_entry`]
+fn unescape_history_entry(line: &str) -> String {
+    let mut entry = String::with_capacity(line.len());
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            entry.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => entry.push('\n'),
+            Some(escaped) => entry.push(escaped),
+            None => entry.push(c),
+        }
+    }
+    entry
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        INTERRUPTED_EXIT_CODE, Mbash,
+        test_support::{CapturingLogger, TestShell, lock_process},
+    };
+    use crate::{error::MbashError, file_system::MemoryFileSystem, output_sink::OutputSink};
+    use std::{env, fs, io::Write, path::PathBuf, sync::atomic::Ordering};
+
+    #[test]
+    fn cd_without_arguments_goes_home() {
+        let mut guard = lock_process();
+        let mut shell = TestShell::new();
+        let home = shell.path("home");
+        fs::create_dir(&home).unwrap();
+        guard.set_var("HOME", Some(home.to_str().unwrap()));
+
+        assert_eq!(shell.mbash.handle_cd_command(&[]), 0);
+        assert_eq!(shell.mbash.current_path, home);
+        assert!(shell.logged("falling back to home directory"));
+    }
+
+    #[test]
+    fn cd_without_arguments_or_home_fails() {
+        let mut guard = lock_process();
+        let mut shell = TestShell::new();
+        guard.set_var("HOME", None);
+
+        assert_eq!(shell.mbash.handle_cd_command(&[]), 1);
+        assert!(shell.logged("Couldn't determine the home directory"));
+    }
+
+    #[test]
+    fn bare_prefix_runs_nothing() {
+        let mut shell = TestShell::new();
+        for input in ["m", "m "] {
+            assert_eq!(shell.run_output(input), "");
+        }
+        assert_eq!(
+            shell
+                .logs()
+                .iter()
+                .filter(|message| message.contains("No command supplied after prefix 'm'"))
+                .count(),
+            2
+        );
+    }
+
+    #[test]
+    fn prefixed_command_is_dispatched() {
+        let mut shell = TestShell::new();
+        shell.write("notes.txt", "");
+
+        assert_eq!(shell.run_output("m ls"), "notes.txt\n");
+        assert_eq!(shell.mbash.last_exit_code, 0);
+    }
+
+    #[test]
+    fn tracking_file_skips_blank_lines() {
+        let mut shell = TestShell::new();
+        shell.write(".mtracking", "a.txt\n\n   \n  b.txt  \n");
+
+        shell.mbash.load_tracking_file().unwrap();
+        assert_eq!(
+            shell.mbash.tracking_files,
+            [shell.path("a.txt"), shell.path("b.txt")].map(|path| path.display().to_string())
+        );
+    }
+
+    #[test]
+    fn pwd_prints_the_current_path() {
+        let mut shell = TestShell::new();
+        let expected = format!("{}\n", shell.mbash.current_path.display());
+
+        assert_eq!(shell.run_output("pwd"), expected);
+        assert_eq!(shell.run_output("m pwd extra"), expected);
+        assert!(shell.logged("'pwd' doesn't take any arguments"));
+    }
+
+    #[test]
+    fn ignore_file_is_loaded_without_blank_lines() {
+        let mut shell = TestShell::new();
+        shell.write(".mignoring", "*.log\n\n  target/  \n");
+
+        shell.mbash.load_ignore_file();
+        assert_eq!(shell.mbash.ignore_patterns, ["*.log", "target/"]);
+    }
+
+    #[test]
+    fn ignore_patterns_match_globs_directories_and_names() {
+        let mut shell = TestShell::new();
+        shell.mbash.ignore_patterns = ["*.log", "target/", "secret.txt"]
+            .map(String::from)
+            .to_vec();
+
+        assert!(shell.mbash.is_ignored("build.log"));
+        assert!(shell.mbash.is_ignored("logs/build.log"));
+        assert!(!shell.mbash.is_ignored("build.txt"));
+
+        assert!(shell.mbash.is_ignored("target"));
+        assert!(shell.mbash.is_ignored("target/debug/mbash"));
+        assert!(!shell.mbash.is_ignored("targets/file"));
+
+        assert!(shell.mbash.is_ignored("secret.txt"));
+        assert!(shell.mbash.is_ignored("config/secret.txt"));
+        assert!(!shell.mbash.is_ignored("secret.txt.bak"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unknown_commands_run_as_programs() {
+        let mut shell = TestShell::new();
+
+        assert_eq!(shell.run_output("printf external"), "external");
+        assert_eq!(shell.mbash.last_exit_code, 0);
+    }
+
+    #[test]
+    fn missing_programs_report_command_not_found() {
+        let mut shell = TestShell::new();
+
+        assert_eq!(shell.run("mbash-test-no-such-program"), 127);
+        assert!(shell.logged("Failed to execute command 'mbash-test-no-such-program'"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn failing_programs_set_the_exit_code() {
+        let mut shell = TestShell::new();
+
+        assert_eq!(shell.run("sh -c 'exit 3'"), 3);
+        assert_eq!(shell.run_output("echo $?"), "3\n");
+        assert_eq!(shell.run("false"), 1);
+    }
+
+    #[test]
+    fn builtins_set_the_exit_code() {
+        let mut shell = TestShell::new();
+
+        assert_eq!(shell.run("cat missing.txt"), 1);
+        assert_eq!(shell.run_output("echo $?"), "1\n");
+        assert_eq!(shell.run_output("echo $?"), "0\n");
+    }
+
+    #[test]
+    fn tilde_expands_to_the_home_directory() {
+        let mut guard = lock_process();
+        let mut shell = TestShell::new();
+        guard.set_var("HOME", Some("/home/tester"));
+
+        assert_eq!(shell.run_output("echo ~"), "/home/tester\n");
+        assert_eq!(shell.run_output("echo ~/sub/dir"), "/home/tester/sub/dir\n");
+        assert_eq!(shell.run_output("echo a~b ~other"), "a~b ~other\n");
+    }
+
+    #[test]
+    fn variables_expand_from_the_environment() {
+        let mut guard = lock_process();
+        let mut shell = TestShell::new();
+        guard.set_var("HOME", Some("/home/tester"));
+        guard.set_var("MBASH_TEST_UNSET", None);
+
+        assert_eq!(shell.run_output("echo $HOME"), "/home/tester\n");
+        assert_eq!(
+            shell.run_output("echo ${PATH}"),
+            format!("{}\n", env::var("PATH").unwrap())
+        );
+        assert_eq!(shell.run_output("echo [$MBASH_TEST_UNSET]"), "[]\n");
+        assert_eq!(shell.run_output("echo \\$HOME"), "$HOME\n");
+    }
+
+    #[test]
+    fn quoted_paths_keep_their_spaces() {
+        let _guard = lock_process();
+        let mut shell = TestShell::new();
+        fs::create_dir(shell.path("a b")).unwrap();
+
+        assert_eq!(shell.run("cd \"a b\""), 0);
+        assert!(shell.mbash.current_path.ends_with("a b"));
+        assert_eq!(shell.run("touch a\\ b.txt"), 0);
+        assert!(shell.path("a b.txt").is_file());
+    }
+
+    #[test]
+    fn history_is_loaded_and_appended_to() {
+        let mut guard = lock_process();
+        let mut shell = TestShell::new();
+        let history_file = shell.path(".mhistory");
+        guard.set_var("HOME", Some(shell.path("").to_str().unwrap()));
+        shell.write(".mhistory", "echo first\nls\n");
+        // The history file stays in the home directory wherever the shell starts
+        shell.mbash.current_path = shell.path("elsewhere");
+
+        shell.mbash.load_history_file();
+        assert_eq!(shell.mbash.history, ["echo first", "ls"]);
+
+        shell.mbash.handle_input("pwd");
+        assert_eq!(
+            fs::read_to_string(history_file).unwrap(),
+            "echo first\nls\npwd\n"
+        );
+    }
+
+    #[test]
+    fn unreadable_history_starts_empty() {
+        let mut guard = lock_process();
+        let mut shell = TestShell::new();
+        guard.set_var("HOME", Some(shell.path("").to_str().unwrap()));
+        shell.mbash.history.push(String::from("stale"));
+        fs::create_dir(shell.path(".mhistory")).unwrap();
+
+        shell.mbash.load_history_file();
+        assert!(shell.mbash.history.is_empty());
+        assert!(shell.logged("starting with an empty history"));
+    }
+
+    #[test]
+    fn history_is_not_saved_without_a_home_directory() {
+        let mut guard = lock_process();
+        let mut shell = TestShell::new();
+        guard.set_var("HOME", None);
+
+        shell.mbash.load_history_file();
+        assert_eq!(shell.mbash.history_file, None);
+        shell.mbash.handle_input("pwd");
+        assert_eq!(shell.mbash.history, ["pwd"]);
+        assert!(!shell.path(".mhistory").exists());
+    }
+
+    #[test]
+    fn multi_line_history_entries_round_trip() {
+        let mut guard = lock_process();
+        let mut shell = TestShell::new();
+        guard.set_var("HOME", Some(shell.path("").to_str().unwrap()));
+        shell.mbash.load_history_file();
+
+        let entries = ["echo 'one\ntwo'", r"echo a\ b", r"echo 'x\ny'"];
+        for entry in entries {
+            shell.mbash.record_history(entry);
+        }
+        assert_eq!(shell.read(".mhistory").lines().count(), entries.len());
+
+        shell.mbash.history.clear();
+        shell.mbash.load_history_file();
+        assert_eq!(shell.mbash.history, entries);
+    }
+
+    #[test]
+    fn builtin_output_feeds_the_next_stage() {
+        let mut shell = TestShell::new();
+        shell.write("a.txt", "");
+        shell.write("b.txt", "");
+
+        assert_eq!(shell.run_output("ls | cat"), "a.txt\nb.txt\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn external_commands_pipe_into_each_other() {
+        let mut shell = TestShell::new();
+
+        assert_eq!(shell.run_output("printf 'b\\na\\n' | sort"), "a\nb\n");
+        assert_eq!(shell.mbash.last_exit_code, 0);
+    }
+
+    #[test]
+    fn output_redirects_truncate_or_append() {
+        let mut shell = TestShell::new();
+        shell.write("out.txt", "stale\n");
+
+        assert_eq!(shell.run_output("echo first > out.txt"), "");
+        assert_eq!(shell.read("out.txt"), "first\n");
+        assert_eq!(shell.run_output("echo second >> out.txt"), "");
+        assert_eq!(shell.read("out.txt"), "first\nsecond\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn external_output_can_be_redirected() {
+        let mut shell = TestShell::new();
+
+        assert_eq!(shell.run("printf external > out.txt"), 0);
+        assert_eq!(shell.read("out.txt"), "external");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn input_redirects_feed_external_commands() {
+        let mut shell = TestShell::new();
+        shell.write("words.txt", "pear\napple\n");
+
+        assert_eq!(shell.run_output("sort < words.txt"), "apple\npear\n");
+    }
+
+    #[test]
+    fn input_redirects_feed_cat() {
+        let mut shell = TestShell::new();
+        shell.write("words.txt", "pear\n");
+
+        assert_eq!(shell.run_output("cat < words.txt"), "pear\n");
+    }
+
+    #[test]
+    fn missing_input_files_are_reported() {
+        let mut shell = TestShell::new();
+
+        assert_eq!(shell.run_output("cat < missing.txt"), "");
+        assert_ne!(shell.mbash.last_exit_code, 0);
+        assert!(shell.logged("missing.txt"));
+    }
+
+    #[test]
+    fn semicolons_run_every_command() {
+        let mut shell = TestShell::new();
+
+        assert_eq!(shell.run_output("cat missing; echo after"), "after\n");
+    }
+
+    #[test]
+    fn and_runs_only_after_success() {
+        let mut shell = TestShell::new();
+
+        assert_eq!(
+            shell.run_output("echo first && echo second"),
+            "first\nsecond\n"
+        );
+        assert_eq!(shell.run_output("cat missing && echo skipped"), "");
+        assert_eq!(shell.mbash.last_exit_code, 1);
+    }
+
+    #[test]
+    fn or_runs_only_after_failure() {
+        let mut shell = TestShell::new();
+
+        assert_eq!(
+            shell.run_output("cat missing || echo recovered"),
+            "recovered\n"
+        );
+        assert_eq!(shell.run_output("echo fine || echo skipped"), "fine\n");
+    }
+
+    #[test]
+    fn registered_commands_are_dispatched() {
+        fn count_args(mbash: &mut Mbash, args: &[&str]) -> i32 {
+            let _ = writeln!(mbash.output, "{} args", args.len());
+            args.len() as i32
+        }
+
+        let mut shell = TestShell::new();
+        shell
+            .mbash
+            .register_command("count", "Count the arguments.", count_args);
+
+        shell.mbash.handle_input("count a b c");
+        assert_eq!(shell.take_output(), "3 args\n");
+        assert_eq!(shell.mbash.last_exit_code(), 3);
+        assert_eq!(
+            shell.mbash.commands["count"].description,
+            "Count the arguments."
+        );
+    }
+
+    #[test]
+    fn prompt_template_fills_in_placeholders() {
+        let mut guard = lock_process();
+        guard.set_var("USER", Some("tester"));
+        guard.set_var("MBASH_PROMPT", Some("{user} {path} [{exit}]$ "));
+        let mut shell = TestShell::new();
+        shell.mbash.load_settings();
+        shell.run("cat missing.txt");
+
+        assert_eq!(
+            shell.mbash.render_prompt(),
+            format!("tester {} [1]$ ", shell.mbash.current_path.display())
+        );
+    }
+
+    #[test]
+    fn prompt_template_defaults_to_the_path() {
+        let mut guard = lock_process();
+        guard.set_var("MBASH_PROMPT", None);
+        let mut shell = TestShell::new();
+        shell.mbash.load_settings();
+
+        assert_eq!(
+            shell.mbash.render_prompt(),
+            format!("mbash@ {}: ", shell.mbash.current_path.display())
+        );
+    }
+
+    #[test]
+    fn arrow_keys_recall_the_loaded_history() {
+        let mut guard = lock_process();
+        let mut shell = TestShell::new();
+        guard.set_var("HOME", Some(shell.path("").to_str().unwrap()));
+        shell.write(".mhistory", "echo one\nls\npwd\n");
+        shell.mbash.load_history_file();
+
+        let history = shell.mbash.editor_history();
+        let entries: Vec<&String> = history.iter().collect();
+        assert_eq!(entries, ["echo one", "ls", "pwd"]);
+    }
+
+    #[test]
+    fn ls_output_can_be_captured() {
+        let mut shell = TestShell::new();
+        shell.write("listed.txt", "");
+
+        let previous = shell.mbash.set_output(OutputSink::Buffer(Vec::new()));
+        assert!(previous.into_buffer().is_empty());
+        shell.mbash.execute_line("ls");
+        let captured = shell.mbash.set_output(OutputSink::Buffer(Vec::new()));
+        assert_eq!(captured.into_buffer(), b"listed.txt\n");
+    }
+
+    #[test]
+    fn failing_cd_returns_a_non_zero_status() {
+        let mut shell = TestShell::new();
+        let before = shell.mbash.current_path.clone();
+
+        assert_eq!(shell.mbash.handle_cd_command(&["missing"]), 1);
+        assert_eq!(shell.run_output("cd missing || echo failed"), "failed\n");
+        assert_eq!(shell.mbash.current_path, before);
+    }
+
+    #[test]
+    fn cd_dash_returns_to_the_previous_directory() {
+        let _guard = lock_process();
+        let mut shell = TestShell::new();
+        shell.run("mkdir sub");
+        let start = shell.mbash.current_path.clone();
+
+        assert_eq!(shell.run("cd sub"), 0);
+        assert_eq!(shell.mbash.current_path, start.join("sub"));
+        assert_eq!(shell.run_output("cd -"), format!("{}\n", start.display()));
+        assert_eq!(shell.mbash.current_path, start);
+    }
+
+    #[test]
+    fn cd_dash_without_a_previous_directory_fails() {
+        let mut shell = TestShell::new();
+        let start = shell.mbash.current_path.clone();
+
+        assert_eq!(shell.run("cd -"), 1);
+        assert_eq!(shell.mbash.current_path, start);
+        assert!(shell.logged("there is no previous directory"));
+    }
+
+    #[test]
+    fn cd_explains_why_it_failed() {
+        let mut shell = TestShell::new();
+        shell.write("file.txt", "");
+
+        assert_eq!(shell.run("cd missing"), 1);
+        assert!(shell.logged("'missing': no such file or directory."));
+        assert_eq!(shell.run("cd file.txt"), 1);
+        assert!(shell.logged("'file.txt': not a directory."));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn cd_reports_permission_denied() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let _guard = lock_process();
+        let mut shell = TestShell::new();
+        let locked = shell.path("locked");
+        fs::create_dir(&locked).unwrap();
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o000)).unwrap();
+        // Privileged users can enter the directory anyway, so there's nothing to check
+        if fs::read_dir(&locked).is_ok() {
+            return;
+        }
+
+        assert_eq!(shell.run("cd locked"), 1);
+        assert!(shell.logged("'locked': permission denied."));
+        fs::set_permissions(&locked, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    #[test]
+    fn rc_file_defines_aliases() {
+        let mut guard = lock_process();
+        let mut shell = TestShell::new();
+        shell.write(".mbashrc", "# greetings\n\nalias greet='echo hello'\n");
+        guard.set_var("HOME", Some(shell.path("").to_str().unwrap()));
+
+        shell.mbash.load_rc_file();
+        assert_eq!(shell.run_output("greet"), "hello\n");
+    }
+
+    #[test]
+    fn missing_rc_file_is_skipped() {
+        let mut guard = lock_process();
+        let mut shell = TestShell::new();
+        guard.set_var("HOME", Some(shell.path("").to_str().unwrap()));
+
+        shell.mbash.load_rc_file();
+        assert!(shell.logged("No '"));
+        assert!(
+            !shell
+                .logs()
+                .iter()
+                .any(|message| message.starts_with("[ERROR]"))
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn losing_the_working_directory_is_reported() {
+        let _guard = lock_process();
+        let mut shell = TestShell::new();
+        let doomed = shell.path("doomed");
+        fs::create_dir(&doomed).unwrap();
+        env::set_current_dir(&doomed).unwrap();
+        fs::remove_dir(&doomed).unwrap();
+
+        assert!(matches!(
+            shell.mbash.set_current_dir(),
+            Err(MbashError::CurrentDir(_))
+        ));
+        assert!(shell.logged("Failed to fetch current directory path."));
+    }
+
+    #[test]
+    fn loading_tracking_file_collapses_duplicate_paths() {
+        let mut shell = TestShell::new();
+        shell.write(".mtracking", "./a.txt\na.txt\n");
+
+        shell.mbash.load_tracking_file().unwrap();
+        assert_eq!(
+            shell.mbash.tracking_files,
+            [shell.path("a.txt").to_string_lossy()]
+        );
+    }
+
+    #[test]
+    fn interrupt_skips_the_rest_of_a_command_list() {
+        let mut shell = TestShell::new();
+        shell.mbash.interrupted.store(true, Ordering::Relaxed);
+
+        assert_eq!(shell.run_output("echo one; echo two"), "");
+        assert_eq!(shell.mbash.last_exit_code, INTERRUPTED_EXIT_CODE);
+        assert!(shell.logged("Interrupted, skipping the rest of 'echo one; echo two'."));
+    }
+
+    #[test]
+    fn interrupt_stops_a_script() {
+        let mut shell = TestShell::new();
+        shell.write("script.mbash", "touch first\ntouch second\n");
+        shell.mbash.interrupted.store(true, Ordering::Relaxed);
+
+        shell.mbash.run_script(&shell.path("script.mbash")).unwrap();
+        assert!(!shell.path("first").exists());
+        assert!(!shell.path("second").exists());
+    }
+
+    #[test]
+    fn braces_expand_before_dispatch() {
+        let mut shell = TestShell::new();
+
+        assert_eq!(shell.run("touch file{1..3}.txt"), 0);
+        for name in ["file1.txt", "file2.txt", "file3.txt"] {
+            assert!(shell.path(name).is_file());
+        }
+        assert_eq!(shell.run("echo {1..3000000000}"), 1);
+        assert!(shell.logged("expands to more than"));
+    }
+
+    #[test]
+    fn set_prefix_changes_the_internal_command_prefix() {
+        let mut shell = TestShell::new();
+        shell.write("notes.txt", "hi\n");
+
+        assert_eq!(shell.run("set prefix mb"), 0);
+        assert_eq!(shell.mbash.internal_command_prefix, "mb");
+        assert_eq!(shell.run_output("mb cat notes.txt"), "hi\n");
+        assert_eq!(shell.run_output("cat notes.txt"), "hi\n");
+    }
+
+    #[test]
+    fn prefix_is_read_from_the_environment() {
+        let mut guard = lock_process();
+        guard.set_var("MBASH_PREFIX", Some("int"));
+        let mut shell = TestShell::new();
+        shell.mbash.load_settings();
+
+        assert_eq!(shell.mbash.internal_command_prefix, "int");
+        assert_eq!(shell.run_output("int echo hi"), "hi\n");
+    }
+
+    #[test]
+    fn bad_prefixes_are_refused_or_warned_about() {
+        let mut shell = TestShell::new();
+
+        assert_eq!(shell.run("set prefix ''"), 1);
+        assert!(shell.logged("The command prefix must be a single non-empty word"));
+        assert_eq!(shell.mbash.internal_command_prefix, "m");
+
+        assert_eq!(shell.run("set prefix ls"), 0);
+        assert!(shell.logged("The command prefix 'ls' shadows the builtin"));
+    }
+
+    #[test]
+    fn workspace_file_names_come_from_the_environment() {
+        let mut guard = lock_process();
+        guard.set_var("MBASH_TRACKING_FILE", Some(".custom-tracking"));
+        guard.set_var("MBASH_IGNORE_FILE", Some(".custom-ignoring"));
+        let mut shell = TestShell::new();
+        shell.mbash.load_workspace_file_names();
+
+        assert_eq!(shell.run("init"), 0);
+        assert!(shell.path(".custom-tracking").is_file());
+        assert!(shell.path(".custom-ignoring").is_file());
+        assert!(!shell.path(".mtracking").exists());
+        assert!(!shell.path(".mignoring").exists());
+
+        shell.write(".custom-ignoring", "*.log\n");
+        shell.write("notes.txt", "");
+        shell.write("debug.log", "");
+        shell.mbash.load_ignore_file();
+        assert!(shell.mbash.is_ignored("debug.log"));
+        assert_eq!(shell.run("add notes.txt"), 0);
+        shell.mbash.save_tracking_changes();
+        assert_eq!(shell.read(".custom-tracking"), "notes.txt\n");
+    }
+
+    #[test]
+    fn scripts_join_continued_lines() {
+        let mut shell = TestShell::new();
+        shell.write("script.mbash", "echo one \\\n  two\necho 'three\nfour'\n");
+
+        shell.mbash.run_script(&shell.path("script.mbash")).unwrap();
+        assert_eq!(shell.take_output(), "one two\nthree\nfour\n");
+    }
+
+    #[test]
+    fn unreadable_tracking_file_is_a_tracking_load_error() {
+        let mut shell = TestShell::new();
+        fs::write(shell.path(".mtracking"), b"\xff\xfe\n").unwrap();
+
+        match shell.mbash.refresh_workspace() {
+            Err(MbashError::TrackingLoad { path, .. }) => {
+                assert_eq!(path, shell.path(".mtracking"))
+            }
+            other => panic!("expected a tracking load error, got {:?}", other),
+        }
+    }
+
+    /// A shell working in `/work` on `file_system`, so nothing it does reaches the disk
+    fn memory_shell(file_system: &MemoryFileSystem) -> Mbash {
+        file_system.add_dir("/work");
+        let mut mbash = Mbash::with_file_system(
+            Box::new(CapturingLogger::default()),
+            Box::new(file_system.clone()),
+        );
+        mbash.current_path = PathBuf::from("/work");
+        mbash.set_output(OutputSink::Buffer(Vec::new()));
+        mbash
+    }
+
+    fn take_output(mbash: &mut Mbash) -> String {
+        let output = mbash.set_output(OutputSink::Buffer(Vec::new()));
+        String::from_utf8(output.into_buffer()).unwrap()
+    }
+
+    #[test]
+    fn ls_lists_an_in_memory_directory() {
+        let file_system = MemoryFileSystem::new();
+        file_system.add_file("/work/notes.txt", "hello");
+        file_system.add_dir("/work/sub");
+        file_system.add_file("/work/.hidden", "");
+        let mut mbash = memory_shell(&file_system);
+
+        mbash.execute_line("ls");
+        assert_eq!(mbash.last_exit_code, 0);
+        assert_eq!(take_output(&mut mbash), "notes.txt\n[DIR] sub\n");
+    }
+
+    #[test]
+    fn tracking_works_on_an_in_memory_workspace() {
+        let file_system = MemoryFileSystem::new();
+        file_system.add_file("/work/notes.txt", "hello");
+        let mut mbash = memory_shell(&file_system);
+
+        mbash.execute_line("init");
+        assert_eq!(mbash.last_exit_code, 0);
+        assert!(file_system.exists("/work/.mignoring"));
+        mbash.execute_line("add notes.txt");
+        mbash.execute_line("save");
+        assert_eq!(
+            file_system.contents("/work/.mtracking").unwrap(),
+            b"notes.txt\n"
+        );
+
+        file_system.add_file("/work/notes.txt", "changed");
+        take_output(&mut mbash);
+        mbash.execute_line("status");
+        assert!(take_output(&mut mbash).contains("modified"));
+    }
+
+    #[test]
+    fn add_expands_patterns_on_an_in_memory_workspace() {
+        let file_system = MemoryFileSystem::new();
+        file_system.add_file("/work/notes.txt", "");
+        file_system.add_file("/work/todo.txt", "");
+        file_system.add_file("/work/image.png", "");
+        let mut mbash = memory_shell(&file_system);
+
+        mbash.execute_line("init");
+        mbash.execute_line("add '*.txt'");
+        assert_eq!(mbash.last_exit_code, 0);
+        mbash.execute_line("save");
+        assert_eq!(
+            file_system.contents("/work/.mtracking").unwrap(),
+            b"notes.txt\ntodo.txt\n"
+        );
     }
 }
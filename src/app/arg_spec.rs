@@ -0,0 +1,190 @@
+use logger::error;
+
+use super::Mbash;
+
+/// The flags and operand count a builtin accepts. Flags may appear anywhere before a `--`, and
+/// single-letter ones can be combined, so `-rf` is the same as `-r -f`.
+pub(super) struct ArgSpec {
+    pub(super) name: &'static str,
+    /// Each entry lists the spellings of one flag, like `&["-a", "--all"]`
+    pub(super) flags: &'static [&'static [&'static str]],
+    /// What an operand is, used in usage errors
+    pub(super) operand: &'static str,
+    pub(super) min_operands: usize,
+    pub(super) max_operands: Option<usize>,
+}
+
+/// The flags and operands found by [`ArgSpec::parse`]
+pub(super) struct ParsedArgs<'a> {
+    flags: Vec<&'static [&'static str]>,
+    pub(super) operands: Vec<&'a str>,
+}
+
+impl ParsedArgs<'_> {
+    /// Tells whether the flag was given under any of its spellings
+    pub(super) fn has(&self, flag: &str) -> bool {
+        self.flags.iter().any(|spellings| spellings.contains(&flag))
+    }
+}
+
+enum ArgError {
+    UnknownFlag(String),
+    TooFewOperands,
+    TooManyOperands,
+}
+
+impl ArgSpec {
+    fn parse<'a>(&self, args: &[&'a str]) -> Result<ParsedArgs<'a>, ArgError> {
+        let mut parsed = ParsedArgs {
+            flags: Vec::new(),
+            operands: Vec::new(),
+        };
+
+        let mut args = args.iter();
+        while let Some(&arg) = args.next() {
+            if arg == "--" {
+                parsed.operands.extend(args);
+                break;
+            }
+            // A lone `-` is an operand, such as the previous directory for `cd`
+            if !arg.starts_with('-') || arg == "-" {
+                parsed.operands.push(arg);
+                continue;
+            }
+
+            if let Some(flag) = self.find_flag(arg) {
+                parsed.flags.push(flag);
+                continue;
+            }
+            if arg.starts_with("--") {
+                return Err(ArgError::UnknownFlag(arg.to_string()));
+            }
+            for letter in arg[1..].chars() {
+                let short_flag = format!("-{}", letter);
+                match self.find_flag(&short_flag) {
+                    Some(flag) => parsed.flags.push(flag),
+                    None => return Err(ArgError::UnknownFlag(short_flag)),
+                }
+            }
+        }
+
+        let operand_count = parsed.operands.len();
+        if operand_count < self.min_operands {
+            return Err(ArgError::TooFewOperands);
+        }
+        if self.max_operands.is_some_and(|max| operand_count > max) {
+            return Err(ArgError::TooManyOperands);
+        }
+        Ok(parsed)
+    }
+
+    fn find_flag(&self, arg: &str) -> Option<&'static [&'static str]> {
+        self.flags
+            .iter()
+            .copied()
+            .find(|spellings| spellings.contains(&arg))
+    }
+
+    /// Describes a misuse, to follow `'<name>' command`
+    fn describe(&self, error: &ArgError) -> String {
+        let plural = |count: usize| match count {
+            1 => format!("one {}", self.operand),
+            _ => format!("{} {}s", count, self.operand),
+        };
+
+        match error {
+            ArgError::UnknownFlag(flag) => format!("doesn't accept the '{}' flag", flag),
+            ArgError::TooFewOperands if self.max_operands == Some(self.min_operands) => {
+                format!("requires {}", plural(self.min_operands))
+            }
+            ArgError::TooFewOperands => format!("requires at least {}", plural(self.min_operands)),
+            ArgError::TooManyOperands => match self.max_operands {
+                Some(0) => String::from("doesn't take arguments"),
+                Some(1) => format!("accepts a single {}", self.operand),
+                Some(max) => format!("accepts at most {}", plural(max)),
+                None => unreachable!("an unbounded command can't get too many operands"),
+            },
+        }
+    }
+}
+
+impl Mbash {
+    /// Checks `args` against `spec`, logging what's wrong along with the command's usage
+    pub(super) fn parse_args<'a>(
+        &self,
+        spec: &ArgSpec,
+        args: &[&'a str],
+    ) -> Option<ParsedArgs<'a>> {
+        match spec.parse(args) {
+            Ok(parsed) => Some(parsed),
+            Err(e) => {
+                let usage = self
+                    .commands
+                    .get(spec.name)
+                    .map_or(spec.name, |command| command.usage.as_str());
+                error!(
+                    self.logger,
+                    "'{}' command {} [usage: {}].",
+                    spec.name,
+                    spec.describe(&e),
+                    usage
+                );
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_support::TestShell;
+    use super::{ArgError, ArgSpec};
+
+    const SPEC: ArgSpec = ArgSpec {
+        name: "demo",
+        flags: &[&["-a", "--all"], &["-l"]],
+        operand: "file",
+        min_operands: 1,
+        max_operands: Some(2),
+    };
+
+    #[test]
+    fn flags_combine_and_stop_at_double_dash() {
+        let parsed = SPEC.parse(&["-la", "one", "--", "-l"]).ok().unwrap();
+        assert!(parsed.has("--all"));
+        assert!(parsed.has("-l"));
+        assert_eq!(parsed.operands, ["one", "-l"]);
+
+        let parsed = SPEC.parse(&["-"]).ok().unwrap();
+        assert_eq!(parsed.operands, ["-"]);
+    }
+
+    #[test]
+    fn misuse_is_described() {
+        let describe = |args: &[&str]| match SPEC.parse(args) {
+            Ok(_) => panic!("{:?} should be refused", args),
+            Err(e) => SPEC.describe(&e),
+        };
+
+        assert_eq!(describe(&["-x", "one"]), "doesn't accept the '-x' flag");
+        assert_eq!(
+            describe(&["--long", "one"]),
+            "doesn't accept the '--long' flag"
+        );
+        assert_eq!(describe(&[]), "requires at least one file");
+        assert_eq!(describe(&["a", "b", "c"]), "accepts at most 2 files");
+        assert!(matches!(SPEC.parse(&[]), Err(ArgError::TooFewOperands)));
+    }
+
+    #[test]
+    fn wrong_arity_logs_the_usage() {
+        let mut shell = TestShell::new();
+
+        assert_eq!(shell.run("cd one two"), 1);
+        assert!(shell.logged("'cd' command accepts a single directory [usage: cd [directory|-]]."));
+        assert_eq!(shell.run("mkdir"), 1);
+        assert!(shell.logged("'mkdir' command requires at least one directory [usage: mkdir"));
+        assert_eq!(shell.run("ls -z"), 1);
+        assert!(shell.logged("'ls' command doesn't accept the '-z' flag [usage: ls"));
+    }
+}
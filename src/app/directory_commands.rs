@@ -0,0 +1,88 @@
+use logger::{debug, error};
+use std::io::Write;
+
+use super::Mbash;
+
+impl Mbash {
+    pub(super) fn handle_pushd_command(&mut self, args: &[&str]) -> i32 {
+        let [directory] = args else {
+            error!(
+                self.logger,
+                "'pushd' command requires a single directory [pushd <directory>]."
+            );
+            return 1;
+        };
+
+        let previous_path = self.current_path.clone();
+        if self.change_directory(directory) != 0 {
+            return 1;
+        }
+
+        self.dir_stack.push(previous_path);
+        self.handle_dirs_command(&[])
+    }
+
+    pub(super) fn handle_popd_command(&mut self, _args: &[&str]) -> i32 {
+        let Some(directory) = self.dir_stack.pop() else {
+            error!(self.logger, "'popd' failed, the directory stack is empty.");
+            return 1;
+        };
+
+        if self.change_directory(&directory.to_string_lossy()) != 0 {
+            debug!(
+                self.logger,
+                "Keeping '{}' on the directory stack.",
+                directory.display()
+            );
+            self.dir_stack.push(directory);
+            return 1;
+        }
+
+        self.handle_dirs_command(&[])
+    }
+
+    pub(super) fn handle_dirs_command(&mut self, _args: &[&str]) -> i32 {
+        let stack: Vec<String> = std::iter::once(&self.current_path)
+            .chain(self.dir_stack.iter().rev())
+            .map(|path| path.display().to_string())
+            .collect();
+
+        let _ = writeln!(self.output, "{}", stack.join(" "));
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_support::{TestShell, lock_process};
+
+    #[test]
+    fn pushd_and_popd_round_trip() {
+        let _guard = lock_process();
+        let mut shell = TestShell::new();
+        shell.run("mkdir sub");
+        let start = shell.mbash.current_path.clone();
+        let sub = shell.path("sub");
+
+        assert_eq!(
+            shell.run_output("pushd sub"),
+            format!("{} {}\n", sub.display(), start.display())
+        );
+        assert_eq!(shell.mbash.current_path, sub);
+        assert_eq!(shell.mbash.dir_stack, vec![start.clone()]);
+
+        assert_eq!(shell.run_output("popd"), format!("{}\n", start.display()));
+        assert_eq!(shell.mbash.current_path, start);
+        assert!(shell.mbash.dir_stack.is_empty());
+    }
+
+    #[test]
+    fn popd_on_an_empty_stack_fails() {
+        let mut shell = TestShell::new();
+        let start = shell.mbash.current_path.clone();
+
+        assert_eq!(shell.run("popd"), 1);
+        assert_eq!(shell.mbash.current_path, start);
+        assert!(shell.logged("the directory stack is empty"));
+    }
+}
@@ -0,0 +1,171 @@
+use logger::error;
+use std::{
+    fs::{self, DirEntry},
+    io::Write,
+    path::Path,
+};
+
+use super::Mbash;
+use crate::helper_functions;
+
+struct UsageOptions {
+    human_readable: bool,
+    max_depth: Option<usize>,
+}
+
+impl Mbash {
+    pub(super) fn handle_du_command(&mut self, args: &[&str]) -> i32 {
+        let mut options = UsageOptions {
+            human_readable: false,
+            max_depth: None,
+        };
+        let mut target = None;
+        let mut args = args.iter();
+
+        while let Some(arg) = args.next() {
+            match *arg {
+                "-h" => options.human_readable = true,
+                "--max-depth" => match args.next().and_then(|depth| depth.parse().ok()) {
+                    Some(depth) => options.max_depth = Some(depth),
+                    None => {
+                        error!(
+                            self.logger,
+                            "'du' expects a depth after --max-depth [du [-h] [--max-depth depth] [path]]."
+                        );
+                        return 1;
+                    }
+                },
+                _ if target.is_none() => target = Some(*arg),
+                _ => {
+                    error!(
+                        self.logger,
+                        "'du' command accepts a single path [du [-h] [--max-depth depth] [path]]."
+                    );
+                    return 1;
+                }
+            }
+        }
+
+        let (directory, relative_directory) = match target {
+            Some(target) => (self.resolve_path(target), Path::new(target)),
+            None => (self.current_path.clone(), Path::new("")),
+        };
+
+        let mut status = 0;
+        let total = self.directory_usage(&directory, relative_directory, 1, &options, &mut status);
+        let _ = writeln!(self.output, "{}\ttotal", format_size(total, &options));
+        status
+    }
+
+    /// Sums the sizes of the files under `directory`, printing every subdirectory's total after
+    /// its contents as long as it is within `max_depth`. Ignored entries don't count.
+    fn directory_usage(
+        &mut self,
+        directory: &Path,
+        relative_directory: &Path,
+        depth: usize,
+        options: &UsageOptions,
+        status: &mut i32,
+    ) -> u64 {
+        let mut entries: Vec<DirEntry> = match fs::read_dir(directory) {
+            Ok(entries) => entries.filter_map(Result::ok).collect(),
+            Err(e) => {
+                error!(
+                    self.logger,
+                    "Failed to read directory '{}': '{}'.",
+                    directory.display(),
+                    e
+                );
+                *status = 1;
+                return 0;
+            }
+        };
+        entries.sort_by_key(DirEntry::file_name);
+
+        let mut total = 0;
+        for entry in entries {
+            let relative_path = relative_directory.join(entry.file_name());
+            if self.is_ignored(&relative_path.to_string_lossy()) {
+                continue;
+            }
+
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+
+            if !metadata.is_dir() {
+                total += metadata.len();
+                continue;
+            }
+
+            let size =
+                self.directory_usage(&entry.path(), &relative_path, depth + 1, options, status);
+            if options.max_depth.is_none_or(|max_depth| depth <= max_depth) {
+                let _ = writeln!(
+                    self.output,
+                    "{}\t{}",
+                    format_size(size, options),
+                    relative_path.display()
+                );
+            }
+            total += size;
+        }
+
+        total
+    }
+}
+
+fn format_size(size: u64, options: &UsageOptions) -> String {
+    if options.human_readable {
+        helper_functions::human_readable_size(size)
+    } else {
+        size.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_support::TestShell;
+
+    fn sample_tree() -> TestShell {
+        let shell = TestShell::new();
+        shell.write("a.txt", "hello");
+        shell.write("sub/b.txt", "0123456789");
+        shell.write("sub/deep/c.txt", "abc");
+        shell
+    }
+
+    #[test]
+    fn du_totals_every_directory() {
+        let mut shell = sample_tree();
+
+        assert_eq!(shell.run_output("du"), "3\tsub/deep\n13\tsub\n18\ttotal\n");
+        assert_eq!(shell.run_output("du sub"), "3\tsub/deep\n13\ttotal\n");
+    }
+
+    #[test]
+    fn du_limits_the_listed_depth() {
+        let mut shell = sample_tree();
+
+        assert_eq!(shell.run_output("du --max-depth 1"), "13\tsub\n18\ttotal\n");
+        assert_eq!(shell.run_output("du --max-depth 0"), "18\ttotal\n");
+    }
+
+    #[test]
+    fn du_skips_ignored_entries() {
+        let mut shell = sample_tree();
+        shell.write(".mignoring", "sub\n");
+        shell.mbash.refresh_workspace().unwrap();
+
+        // Only a.txt and the 4 bytes of the ignore file itself are left
+        assert_eq!(shell.run_output("du"), "9\ttotal\n");
+    }
+
+    #[test]
+    fn du_prints_human_readable_sizes() {
+        let mut shell = TestShell::new();
+        shell.write("big.bin", &"x".repeat(2048));
+
+        assert_eq!(shell.run_output("du -h"), "2.0K\ttotal\n");
+    }
+}
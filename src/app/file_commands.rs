@@ -0,0 +1,700 @@
+use logger::{debug, error, info};
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use super::{Mbash, arg_spec::ArgSpec};
+use crate::helper_functions;
+
+const MKDIR_ARGS: ArgSpec = ArgSpec {
+    name: "mkdir",
+    flags: &[&["-p"]],
+    operand: "directory",
+    min_operands: 1,
+    max_operands: None,
+};
+const RM_ARGS: ArgSpec = ArgSpec {
+    name: "rm",
+    flags: &[&["-r"], &["-f"]],
+    operand: "path",
+    min_operands: 1,
+    max_operands: None,
+};
+const TOUCH_ARGS: ArgSpec = ArgSpec {
+    name: "touch",
+    flags: &[],
+    operand: "file",
+    min_operands: 1,
+    max_operands: None,
+};
+const CHMOD_ARGS: ArgSpec = ArgSpec {
+    name: "chmod",
+    flags: &[],
+    operand: "argument",
+    min_operands: 2,
+    max_operands: None,
+};
+const CP_ARGS: ArgSpec = ArgSpec {
+    name: "cp",
+    flags: &[&["-f"]],
+    operand: "path",
+    min_operands: 2,
+    max_operands: Some(2),
+};
+const MV_ARGS: ArgSpec = ArgSpec {
+    name: "mv",
+    ..CP_ARGS
+};
+
+impl Mbash {
+    pub(super) fn handle_mkdir_command(&mut self, args: &[&str]) -> i32 {
+        let Some(args) = self.parse_args(&MKDIR_ARGS, args) else {
+            return 1;
+        };
+        let create_parents = args.has("-p");
+
+        let mut status = 0;
+        for directory in &args.operands {
+            let path = self.resolve_path(directory);
+            let creation_result = if create_parents {
+                fs::create_dir_all(&path)
+            } else {
+                fs::create_dir(&path)
+            };
+
+            match creation_result {
+                Ok(()) => debug!(self.logger, "Created directory '{}'.", path.display()),
+                Err(e) => {
+                    error!(
+                        self.logger,
+                        "Failed to create directory '{}': '{}'.",
+                        path.display(),
+                        e
+                    );
+                    status = 1;
+                }
+            }
+        }
+
+        status
+    }
+
+    pub(super) fn handle_rm_command(&mut self, args: &[&str]) -> i32 {
+        let Some(args) = self.parse_args(&RM_ARGS, args) else {
+            return 1;
+        };
+        let (recursive, force) = (args.has("-r"), args.has("-f"));
+
+        let mut status = 0;
+        for target in args.operands {
+            if self.is_ignored(target) {
+                error!(
+                    self.logger,
+                    "Refusing to remove '{}', it matches an ignored entry.", target
+                );
+                status = 1;
+                continue;
+            }
+
+            let path = self.resolve_path(target);
+            if self.skip_for_dry_run(&format!("remove '{}'", path.display())) {
+                continue;
+            }
+
+            let recursive_removal = recursive
+                && self
+                    .file_system
+                    .symlink_metadata(&path)
+                    .is_ok_and(|metadata| metadata.is_dir());
+            if recursive_removal
+                && !force
+                && !self.confirm(&format!("Delete {} recursively?", path.display()))
+            {
+                info!(
+                    self.logger,
+                    "Kept '{}', use -f to remove it without asking.",
+                    path.display()
+                );
+                status = 1;
+                continue;
+            }
+
+            let removal_result = if recursive_removal {
+                self.file_system.remove_dir_all(&path)
+            } else {
+                self.file_system.remove_file(&path)
+            };
+
+            match removal_result {
+                Ok(()) => debug!(self.logger, "Removed '{}'.", path.display()),
+                Err(e) => {
+                    error!(
+                        self.logger,
+                        "Failed to remove '{}': '{}'.",
+                        path.display(),
+                        e
+                    );
+                    status = 1;
+                }
+            }
+        }
+
+        status
+    }
+
+    pub(super) fn handle_touch_command(&mut self, args: &[&str]) -> i32 {
+        let Some(args) = self.parse_args(&TOUCH_ARGS, args) else {
+            return 1;
+        };
+
+        let mut status = 0;
+        for file_name in args.operands {
+            let path = self.resolve_path(file_name);
+
+            if !path.exists() {
+                helper_functions::attempt_create_file(
+                    &path.to_string_lossy(),
+                    self.logger.as_ref(),
+                );
+                if path.exists() {
+                    debug!(self.logger, "Created '{}'.", path.display());
+                } else {
+                    status = 1;
+                }
+                continue;
+            }
+
+            let touch_result = OpenOptions::new()
+                .append(true)
+                .open(&path)
+                .and_then(|file| file.set_modified(SystemTime::now()));
+
+            match touch_result {
+                Ok(()) => debug!(
+                    self.logger,
+                    "'{}' already exists, updated its modified time.",
+                    path.display()
+                ),
+                Err(e) => {
+                    error!(
+                        self.logger,
+                        "Failed to update the modified time of '{}': '{}'.",
+                        path.display(),
+                        e
+                    );
+                    status = 1;
+                }
+            }
+        }
+
+        status
+    }
+
+    pub(super) fn handle_chmod_command(&mut self, args: &[&str]) -> i32 {
+        let Some(args) = self.parse_args(&CHMOD_ARGS, args) else {
+            return 1;
+        };
+        let Some((raw_mode, file_names)) = args.operands.split_first() else {
+            unreachable!("the argument spec requires a mode and files");
+        };
+
+        let mode = match u32::from_str_radix(raw_mode, 8) {
+            Ok(mode) if mode <= 0o7777 => mode,
+            _ => {
+                error!(
+                    self.logger,
+                    "'{}' isn't an octal mode like 644 or 755.", raw_mode
+                );
+                return 1;
+            }
+        };
+
+        let mut status = 0;
+        for file_name in file_names {
+            let path = self.resolve_path(file_name);
+            match set_mode(&path, mode) {
+                Ok(()) => debug!(
+                    self.logger,
+                    "Changed the mode of '{}' to {:04o}.",
+                    path.display(),
+                    mode
+                ),
+                Err(e) if e.kind() == io::ErrorKind::Unsupported => {
+                    error!(self.logger, "'chmod' isn't supported on this platform.");
+                    return 1;
+                }
+                Err(e) => {
+                    error!(
+                        self.logger,
+                        "Failed to change the mode of '{}': '{}'.",
+                        path.display(),
+                        e
+                    );
+                    status = 1;
+                }
+            }
+        }
+
+        status
+    }
+
+    pub(super) fn handle_cat_command(&mut self, args: &[&str]) -> i32 {
+        if args.is_empty() {
+            if let Some(input) = self.input.take() {
+                let _ = self.output.write_all(&input);
+                return 0;
+            }
+
+            error!(
+                self.logger,
+                "'cat' command requires at least one file [cat <file>...]."
+            );
+            return 1;
+        }
+
+        let mut status = 0;
+        for file_name in args {
+            let path = self.resolve_path(file_name);
+
+            let bytes = match fs::read(&path) {
+                Ok(bytes) => bytes,
+                Err(e) => {
+                    error!(self.logger, "Failed to open '{}': '{}'.", path.display(), e);
+                    status = 1;
+                    continue;
+                }
+            };
+
+            let _ = match String::from_utf8(bytes) {
+                Ok(contents) => write!(self.output, "{}", contents),
+                Err(e) => writeln!(
+                    self.output,
+                    "'{}' is not valid UTF-8, skipping {} bytes.",
+                    file_name,
+                    e.as_bytes().len()
+                ),
+            };
+        }
+
+        status
+    }
+
+    pub(super) fn handle_cp_command(&mut self, args: &[&str]) -> i32 {
+        let Some((source, destination)) = self.transfer_paths(&CP_ARGS, args) else {
+            return 1;
+        };
+
+        if source.is_dir() {
+            error!(
+                self.logger,
+                "'{}' is a directory, 'cp' only copies files.",
+                source.display()
+            );
+            return 1;
+        }
+
+        if self.skip_for_dry_run(&format!(
+            "copy '{}' to '{}'",
+            source.display(),
+            destination.display()
+        )) {
+            return 0;
+        }
+
+        match fs::copy(&source, &destination) {
+            Ok(_) => {
+                debug!(
+                    self.logger,
+                    "Copied '{}' to '{}'.",
+                    source.display(),
+                    destination.display()
+                );
+                0
+            }
+            Err(e) => {
+                error!(
+                    self.logger,
+                    "Failed to copy '{}' to '{}': '{}'.",
+                    source.display(),
+                    destination.display(),
+                    e
+                );
+                1
+            }
+        }
+    }
+
+    pub(super) fn handle_mv_command(&mut self, args: &[&str]) -> i32 {
+        let Some((source, destination)) = self.transfer_paths(&MV_ARGS, args) else {
+            return 1;
+        };
+
+        if self.skip_for_dry_run(&format!(
+            "move '{}' to '{}'",
+            source.display(),
+            destination.display()
+        )) {
+            return 0;
+        }
+
+        // Renaming fails across filesystems, in which case the file is copied and the original removed
+        let move_result = fs::rename(&source, &destination).or_else(|e| {
+            if e.kind() != io::ErrorKind::CrossesDevices || source.is_dir() {
+                return Err(e);
+            }
+
+            debug!(
+                self.logger,
+                "Renaming '{}' failed ({}), copying it instead.",
+                source.display(),
+                e
+            );
+            fs::copy(&source, &destination).and_then(|_| fs::remove_file(&source))
+        });
+
+        match move_result {
+            Ok(()) => {
+                debug!(
+                    self.logger,
+                    "Moved '{}' to '{}'.",
+                    source.display(),
+                    destination.display()
+                );
+                0
+            }
+            Err(e) => {
+                error!(
+                    self.logger,
+                    "Failed to move '{}' to '{}': '{}'.",
+                    source.display(),
+                    destination.display(),
+                    e
+                );
+                1
+            }
+        }
+    }
+
+    /// Parses `[-f] <src> <dst>` into resolved paths, placing the source inside `dst` when it is a directory.
+    /// Returns `None` after logging when the arguments are invalid or the destination exists without `-f`.
+    fn transfer_paths(&mut self, spec: &ArgSpec, args: &[&str]) -> Option<(PathBuf, PathBuf)> {
+        let args = self.parse_args(spec, args)?;
+        let force = args.has("-f");
+        let [source, destination] = args.operands.as_slice() else {
+            unreachable!("the argument spec requires a source and a destination");
+        };
+
+        let source = self.resolve_path(source);
+        if !source.exists() {
+            error!(self.logger, "'{}' does not exist.", source.display());
+            return None;
+        }
+
+        let mut destination = self.resolve_path(destination);
+        if destination.is_dir()
+            && let Some(file_name) = source.file_name()
+        {
+            destination.push(file_name);
+        }
+
+        if destination.exists() && !force {
+            error!(
+                self.logger,
+                "'{}' already exists, use -f to overwrite it.",
+                destination.display()
+            );
+            return None;
+        }
+
+        Some((source, destination))
+    }
+}
+
+#[cfg(unix)]
+fn set_mode(path: &Path, mode: u32) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    fs::set_permissions(path, fs::Permissions::from_mode(mode))
+}
+
+#[cfg(not(unix))]
+fn set_mode(_path: &Path, _mode: u32) -> io::Result<()> {
+    Err(io::Error::from(io::ErrorKind::Unsupported))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::{self, File},
+        time::{Duration, SystemTime},
+    };
+
+    use super::super::test_support::TestShell;
+
+    #[test]
+    fn mkdir_creates_each_directory() {
+        let mut shell = TestShell::new();
+
+        assert_eq!(shell.run("mkdir first second"), 0);
+        assert!(shell.path("first").is_dir());
+        assert!(shell.path("second").is_dir());
+    }
+
+    #[test]
+    fn mkdir_needs_p_for_missing_parents() {
+        let mut shell = TestShell::new();
+
+        assert_eq!(shell.run("mkdir a/b/c"), 1);
+        assert!(!shell.path("a").exists());
+        assert!(shell.logged("Failed to create directory"));
+
+        assert_eq!(shell.run("mkdir -p a/b/c"), 0);
+        assert!(shell.path("a/b/c").is_dir());
+    }
+
+    #[test]
+    fn mkdir_continues_after_a_failure() {
+        let mut shell = TestShell::new();
+
+        assert_eq!(shell.run("mkdir missing/child created"), 1);
+        assert!(shell.path("created").is_dir());
+    }
+
+    #[test]
+    fn rm_removes_a_file() {
+        let mut shell = TestShell::new();
+        shell.write("old.txt", "");
+
+        assert_eq!(shell.run("rm old.txt"), 0);
+        assert!(!shell.path("old.txt").exists());
+    }
+
+    #[test]
+    fn rm_refuses_ignored_files() {
+        let mut shell = TestShell::new();
+        shell.write(".mignoring", "keep.txt\n");
+        shell.write("keep.txt", "");
+        shell.mbash.refresh_workspace().unwrap();
+
+        assert_eq!(shell.run("rm keep.txt"), 1);
+        assert!(shell.path("keep.txt").exists());
+        assert!(shell.logged("Refusing to remove 'keep.txt'"));
+    }
+
+    #[test]
+    fn rm_needs_r_for_directories() {
+        let mut shell = TestShell::new();
+        shell.write("build/out/app", "");
+
+        assert_eq!(shell.run("rm build"), 1);
+        assert!(shell.path("build").exists());
+
+        assert_eq!(shell.run("rm -rf build"), 0);
+        assert!(!shell.path("build").exists());
+    }
+
+    #[test]
+    fn touch_creates_new_files_and_bumps_existing_ones() {
+        let mut shell = TestShell::new();
+        shell.write("old.txt", "kept");
+        let long_ago = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000);
+        File::options()
+            .append(true)
+            .open(shell.path("old.txt"))
+            .and_then(|file| file.set_modified(long_ago))
+            .unwrap();
+
+        assert_eq!(shell.run("touch new.txt old.txt"), 0);
+        assert!(shell.path("new.txt").is_file());
+        assert_eq!(shell.read("old.txt"), "kept");
+        let modified = fs::metadata(shell.path("old.txt"))
+            .and_then(|metadata| metadata.modified())
+            .unwrap();
+        assert!(modified > long_ago);
+    }
+
+    #[test]
+    fn cat_prints_files_in_order() {
+        let mut shell = TestShell::new();
+        shell.write("one.txt", "first\n");
+        shell.write("two.txt", "second\n");
+
+        assert_eq!(shell.run_output("cat one.txt"), "first\n");
+        assert_eq!(shell.run_output("cat one.txt two.txt"), "first\nsecond\n");
+    }
+
+    #[test]
+    fn cat_reports_binary_and_missing_files() {
+        let mut shell = TestShell::new();
+        fs::write(shell.path("blob"), [0xff, 0xfe, 0x00]).unwrap();
+
+        assert_eq!(
+            shell.run_output("cat blob"),
+            "'blob' is not valid UTF-8, skipping 3 bytes.\n"
+        );
+        assert_eq!(shell.run("cat missing.txt"), 1);
+        assert!(shell.logged("Failed to open"));
+    }
+
+    #[test]
+    fn cp_copies_a_file() {
+        let mut shell = TestShell::new();
+        shell.write("source.txt", "contents");
+
+        assert_eq!(shell.run("cp source.txt copy.txt"), 0);
+        assert_eq!(shell.read("source.txt"), "contents");
+        assert_eq!(shell.read("copy.txt"), "contents");
+    }
+
+    #[test]
+    fn mv_moves_a_file_into_a_directory() {
+        let mut shell = TestShell::new();
+        shell.write("source.txt", "contents");
+        shell.run("mkdir archive");
+
+        assert_eq!(shell.run("mv source.txt archive"), 0);
+        assert!(!shell.path("source.txt").exists());
+        assert_eq!(shell.read("archive/source.txt"), "contents");
+    }
+
+    #[test]
+    fn transfers_refuse_to_overwrite_without_f() {
+        let mut shell = TestShell::new();
+        shell.write("source.txt", "new");
+        shell.write("target.txt", "old");
+
+        for command in ["cp", "mv"] {
+            assert_eq!(shell.run(&format!("{} source.txt target.txt", command)), 1);
+            assert_eq!(shell.read("target.txt"), "old");
+        }
+        assert!(shell.logged("already exists, use -f to overwrite it"));
+    }
+
+    #[test]
+    fn transfers_overwrite_with_f() {
+        let mut shell = TestShell::new();
+        shell.write("first.txt", "first");
+        shell.write("second.txt", "second");
+        shell.write("target.txt", "old");
+
+        assert_eq!(shell.run("cp -f first.txt target.txt"), 0);
+        assert_eq!(shell.read("target.txt"), "first");
+        assert_eq!(shell.run("mv -f second.txt target.txt"), 0);
+        assert_eq!(shell.read("target.txt"), "second");
+        assert!(!shell.path("second.txt").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn chmod_sets_octal_modes() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let mut shell = TestShell::new();
+        shell.write("run.sh", "");
+
+        assert_eq!(shell.run("chmod 754 run.sh"), 0);
+        let mode = fs::metadata(shell.path("run.sh"))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o7777, 0o754);
+    }
+
+    #[test]
+    fn chmod_rejects_modes_that_are_not_octal() {
+        let mut shell = TestShell::new();
+        shell.write("run.sh", "");
+
+        assert_eq!(shell.run("chmod 789 run.sh"), 1);
+        assert!(shell.logged("'789' isn't an octal mode like 644 or 755."));
+        assert_eq!(shell.run("chmod 755"), 1);
+    }
+
+    #[test]
+    fn dry_run_rm_keeps_the_file() {
+        let mut shell = TestShell::new();
+        shell.write("notes.txt", "");
+        let path = shell.path("notes.txt");
+
+        assert_eq!(shell.run("set dryrun on"), 0);
+        assert_eq!(
+            shell.run_output("rm notes.txt"),
+            format!("dry run: would remove '{}'\n", path.display())
+        );
+        assert!(path.is_file());
+
+        assert_eq!(shell.run("set dryrun off"), 0);
+        assert_eq!(shell.run("rm notes.txt"), 0);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn dry_run_cp_and_mv_leave_the_files_alone() {
+        let mut shell = TestShell::new();
+        shell.write("a.txt", "a");
+        shell.run("set dryrun on");
+
+        assert!(
+            shell
+                .run_output("cp a.txt b.txt")
+                .starts_with("dry run: would copy")
+        );
+        assert!(
+            shell
+                .run_output("mv a.txt c.txt")
+                .starts_with("dry run: would move")
+        );
+        assert!(shell.path("a.txt").is_file());
+        assert!(!shell.path("b.txt").exists());
+        assert!(!shell.path("c.txt").exists());
+    }
+
+    #[test]
+    fn dryrun_takes_only_on_or_off() {
+        let mut shell = TestShell::new();
+
+        assert_eq!(shell.run("set dryrun maybe"), 1);
+        assert!(!shell.mbash.dry_run);
+    }
+
+    #[test]
+    fn only_yes_answers_confirm() {
+        let shell = TestShell::new();
+
+        assert!(!shell.mbash.read_answer(&mut "n\n".as_bytes()));
+        assert!(!shell.mbash.read_answer(&mut "".as_bytes()));
+        assert!(shell.mbash.read_answer(&mut "Y\n".as_bytes()));
+        assert!(shell.mbash.read_answer(&mut "yes\n".as_bytes()));
+    }
+
+    #[test]
+    fn rm_r_keeps_directories_without_a_confirmation() {
+        let mut shell = TestShell::new();
+        shell.write("sub/notes.txt", "");
+
+        assert_eq!(shell.run("rm -r sub"), 1);
+        assert!(shell.path("sub/notes.txt").is_file());
+        assert!(shell.logged("use -f to remove it without asking"));
+
+        assert_eq!(shell.run("rm -rf sub"), 0);
+        assert!(!shell.path("sub").exists());
+    }
+
+    #[test]
+    fn rm_r_removes_a_directory_only_when_the_answer_is_yes() {
+        let mut shell = TestShell::new();
+        shell.write("sub/notes.txt", "");
+        shell.mbash.interactive = true;
+
+        shell.mbash.answers = Some(Box::new("n\n".as_bytes()));
+        assert_eq!(shell.run("rm -r sub"), 1);
+        assert!(shell.path("sub/notes.txt").is_file());
+
+        shell.mbash.answers = Some(Box::new("y\n".as_bytes()));
+        assert_eq!(shell.run("rm -r sub"), 0);
+        assert!(!shell.path("sub").exists());
+    }
+}
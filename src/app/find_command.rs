@@ -0,0 +1,162 @@
+use glob::Pattern;
+use logger::error;
+use std::{
+    fs::{self, DirEntry},
+    io::Write,
+    path::Path,
+};
+
+use super::Mbash;
+
+#[derive(Clone, Copy, PartialEq)]
+enum EntryType {
+    File,
+    Directory,
+}
+
+impl Mbash {
+    pub(super) fn handle_find_command(&mut self, args: &[&str]) -> i32 {
+        let mut entry_type = None;
+        let mut raw_pattern = None;
+        let mut args = args.iter();
+
+        while let Some(arg) = args.next() {
+            match *arg {
+                "-type" => match args.next().copied() {
+                    Some("f") => entry_type = Some(EntryType::File),
+                    Some("d") => entry_type = Some(EntryType::Directory),
+                    _ => {
+                        error!(
+                            self.logger,
+                            "'find' command expects 'f' or 'd' after -type [find [-type f|d] <pattern>]."
+                        );
+                        return 1;
+                    }
+                },
+                _ if raw_pattern.is_none() => raw_pattern = Some(*arg),
+                _ => {
+                    error!(
+                        self.logger,
+                        "'find' command accepts a single pattern [find [-type f|d] <pattern>]."
+                    );
+                    return 1;
+                }
+            }
+        }
+
+        let Some(raw_pattern) = raw_pattern else {
+            error!(
+                self.logger,
+                "'find' command requires a pattern [find [-type f|d] <pattern>]."
+            );
+            return 1;
+        };
+
+        let pattern = match Pattern::new(raw_pattern) {
+            Ok(pattern) => pattern,
+            Err(e) => {
+                error!(
+                    self.logger,
+                    "'{}' isn't a valid pattern: '{}'.", raw_pattern, e
+                );
+                return 1;
+            }
+        };
+
+        let root = self.current_path.clone();
+        self.find_entries(&root, Path::new(""), &pattern, entry_type)
+    }
+
+    /// Walks `directory` depth first, printing entries relative to the current path.
+    /// Ignored entries are pruned so their subtrees are never visited.
+    fn find_entries(
+        &mut self,
+        directory: &Path,
+        relative_directory: &Path,
+        pattern: &Pattern,
+        entry_type: Option<EntryType>,
+    ) -> i32 {
+        let mut entries: Vec<DirEntry> = match fs::read_dir(directory) {
+            Ok(entries) => entries.filter_map(Result::ok).collect(),
+            Err(e) => {
+                error!(
+                    self.logger,
+                    "Failed to read directory '{}': '{}'.",
+                    directory.display(),
+                    e
+                );
+                return 1;
+            }
+        };
+        entries.sort_by_key(DirEntry::file_name);
+
+        let mut status = 0;
+        for entry in entries {
+            let relative_path = relative_directory.join(entry.file_name());
+            if self.is_ignored(&relative_path.to_string_lossy()) {
+                continue;
+            }
+
+            let is_directory = entry.file_type().is_ok_and(|file_type| file_type.is_dir());
+            let current_type = if is_directory {
+                EntryType::Directory
+            } else {
+                EntryType::File
+            };
+
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if pattern.matches(&name) && entry_type.is_none_or(|wanted| wanted == current_type) {
+                let _ = writeln!(self.output, "{}", relative_path.display());
+            }
+
+            if is_directory
+                && self.find_entries(&entry.path(), &relative_path, pattern, entry_type) != 0
+            {
+                status = 1;
+            }
+        }
+
+        status
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_support::TestShell;
+
+    /// A tree with matches at several depths and one under an ignored directory
+    fn tree() -> TestShell {
+        let mut shell = TestShell::new();
+        shell.write(".mignoring", "target/\n");
+        for file in [
+            "top.rs",
+            "notes.txt",
+            "src/main.rs",
+            "src/deep/lib.rs",
+            "target/gen.rs",
+        ] {
+            shell.write(file, "");
+        }
+        shell.mbash.refresh_workspace().unwrap();
+        shell
+    }
+
+    #[test]
+    fn find_matches_names_recursively() {
+        let mut shell = tree();
+
+        assert_eq!(
+            shell.run_output("find '*.rs'"),
+            "src/deep/lib.rs\nsrc/main.rs\ntop.rs\n"
+        );
+        assert_eq!(shell.run_output("find 'no?es.*'"), "notes.txt\n");
+    }
+
+    #[test]
+    fn find_filters_by_type() {
+        let mut shell = tree();
+
+        assert_eq!(shell.run_output("find -type d '*'"), "src\nsrc/deep\n");
+        assert_eq!(shell.run_output("find -type f 'deep*'"), "");
+    }
+}
@@ -0,0 +1,164 @@
+use logger::{debug, error};
+use regex::{Regex, RegexBuilder};
+use std::{fs, io::Write};
+
+use super::Mbash;
+
+impl Mbash {
+    pub(super) fn handle_grep_command(&mut self, args: &[&str]) -> i32 {
+        let mut case_insensitive = false;
+        let mut show_line_numbers = true;
+        let mut operands = Vec::new();
+
+        for arg in args {
+            match *arg {
+                "-i" => case_insensitive = true,
+                "-n" => show_line_numbers = !show_line_numbers,
+                _ => operands.push(*arg),
+            }
+        }
+
+        let Some((raw_pattern, file_names)) = operands.split_first() else {
+            error!(
+                self.logger,
+                "'grep' command requires a pattern [grep [-i] [-n] <pattern> [file...]]."
+            );
+            return 1;
+        };
+
+        let pattern = match RegexBuilder::new(raw_pattern)
+            .case_insensitive(case_insensitive)
+            .build()
+        {
+            Ok(pattern) => pattern,
+            Err(e) => {
+                error!(
+                    self.logger,
+                    "'{}' isn't a valid regular expression: '{}'.", raw_pattern, e
+                );
+                return 1;
+            }
+        };
+
+        let mut status = 0;
+        let mut matched = false;
+        if file_names.is_empty() {
+            let Some(input) = self.input.take() else {
+                error!(
+                    self.logger,
+                    "'grep' command requires a file or piped input [grep [-i] [-n] <pattern> [file...]]."
+                );
+                return 1;
+            };
+
+            let contents = String::from_utf8_lossy(&input).into_owned();
+            matched = self.print_matching_lines(&pattern, &contents, None, show_line_numbers);
+        }
+
+        for file_name in file_names {
+            let path = self.resolve_path(file_name);
+            let contents = match fs::read_to_string(&path) {
+                Ok(contents) => contents,
+                Err(e) => {
+                    error!(self.logger, "Failed to read '{}': '{}'.", path.display(), e);
+                    status = 1;
+                    continue;
+                }
+            };
+
+            matched |=
+                self.print_matching_lines(&pattern, &contents, Some(file_name), show_line_numbers);
+        }
+
+        if !matched {
+            debug!(self.logger, "No lines matched '{}'.", raw_pattern);
+            status = 1;
+        }
+
+        status
+    }
+
+    /// Prints the lines of `contents` matching `pattern` as `file:line:text`, returning whether any matched
+    fn print_matching_lines(
+        &mut self,
+        pattern: &Regex,
+        contents: &str,
+        file_name: Option<&str>,
+        show_line_numbers: bool,
+    ) -> bool {
+        let mut matched = false;
+
+        for (index, line) in contents.lines().enumerate() {
+            if !pattern.is_match(line) {
+                continue;
+            }
+
+            matched = true;
+            let mut prefix = String::new();
+            if let Some(file_name) = file_name {
+                prefix.push_str(file_name);
+                prefix.push(':');
+            }
+            if show_line_numbers {
+                prefix.push_str(&format!("{}:", index + 1));
+            }
+
+            let _ = writeln!(self.output, "{}{}", prefix, line);
+        }
+
+        matched
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_support::TestShell;
+
+    fn shell() -> TestShell {
+        let shell = TestShell::new();
+        shell.write("a.txt", "Hello\nbye\nhello again\n");
+        shell.write("b.txt", "nothing\n");
+        shell
+    }
+
+    #[test]
+    fn grep_prints_matches_with_file_and_line() {
+        let mut shell = shell();
+
+        assert_eq!(
+            shell.run_output("grep hello a.txt b.txt"),
+            "a.txt:3:hello again\n"
+        );
+        assert_eq!(shell.mbash.last_exit_code, 0);
+        assert_eq!(
+            shell.run_output("grep -n hello a.txt"),
+            "a.txt:hello again\n"
+        );
+    }
+
+    #[test]
+    fn grep_i_ignores_case() {
+        let mut shell = shell();
+
+        assert_eq!(
+            shell.run_output("grep -i hello a.txt"),
+            "a.txt:1:Hello\na.txt:3:hello again\n"
+        );
+    }
+
+    #[test]
+    fn grep_without_matches_fails() {
+        let mut shell = shell();
+
+        assert_eq!(shell.run_output("grep hello b.txt"), "");
+        assert_eq!(shell.mbash.last_exit_code, 1);
+    }
+
+    #[test]
+    fn grep_rejects_invalid_patterns() {
+        let mut shell = shell();
+
+        assert_eq!(shell.run("grep '(' a.txt"), 1);
+        assert!(shell.logged("isn't a valid regular expression"));
+    }
+}
@@ -0,0 +1,229 @@
+use logger::{debug, error};
+use std::{
+    fs::File,
+    io::Write,
+    process::{Child, ExitStatus, Stdio},
+};
+
+use super::{COMMAND_NOT_FOUND_EXIT_CODE, Mbash, exit_status_code};
+
+/// An external command started with `&` that hasn't been reaped yet
+pub(super) struct Job {
+    id: usize,
+    command_line: String,
+    child: Child,
+}
+
+impl Mbash {
+    /// Starts the external command of `segment` without waiting for it. Builtins and pipelines
+    /// need the shell while they run, so only a single external command can go to the background.
+    pub(super) fn execute_in_background(&mut self, segment: &str) {
+        let Some(mut stages) = self.parse_segment(segment) else {
+            return;
+        };
+        if stages.len() != 1 {
+            if !stages.is_empty() {
+                error!(self.logger, "Pipelines can't run in the background.");
+                self.last_exit_code = 1;
+            }
+            return;
+        }
+
+        let stage = stages.remove(0);
+        let words = self.expand_aliases(stage.words);
+        let Some((program, args)) = words.split_first() else {
+            debug!(self.logger, "Alias expansion resulted in an empty command.");
+            return;
+        };
+        if self.commands.contains_key(program) {
+            error!(
+                self.logger,
+                "'{}' is a builtin, only external commands can run in the background.", program
+            );
+            self.last_exit_code = 1;
+            return;
+        }
+
+        let mut command = match self.find_plugin(program) {
+            Some(plugin_path) => self.plugin_command(&plugin_path),
+            None => self.external_command(program),
+        };
+        command.args(args).stdin(Stdio::null());
+
+        if let Some(input_path) = &stage.input_redirect {
+            let path = self.resolve_path(input_path);
+            match File::open(&path) {
+                Ok(file) => {
+                    command.stdin(file);
+                }
+                Err(e) => {
+                    error!(self.logger, "Failed to read '{}': '{}'.", path.display(), e);
+                    self.last_exit_code = 1;
+                    return;
+                }
+            }
+        }
+        if let Some(redirect) = &stage.output_redirect {
+            let Some(file) = self.open_output_redirect(redirect) else {
+                self.last_exit_code = 1;
+                return;
+            };
+            command.stdout(file);
+        }
+
+        match command.spawn() {
+            Ok(child) => {
+                let id = self.jobs.iter().map(|job| job.id).max().unwrap_or(0) + 1;
+                let _ = writeln!(self.output, "[{}] {}", id, child.id());
+                self.jobs.push(Job {
+                    id,
+                    command_line: segment.trim().to_string(),
+                    child,
+                });
+                self.last_exit_code = 0;
+            }
+            Err(e) => {
+                error!(
+                    self.logger,
+                    "Failed to execute command '{}': {}", program, e
+                );
+                self.last_exit_code = COMMAND_NOT_FOUND_EXIT_CODE;
+            }
+        }
+    }
+
+    /// Reports and forgets the background jobs that have finished since the last check
+    pub(super) fn reap_jobs(&mut self) {
+        let mut index = 0;
+        while index < self.jobs.len() {
+            match self.jobs[index].child.try_wait() {
+                Ok(Some(status)) => {
+                    let job = self.jobs.remove(index);
+                    self.report_finished_job(&job, status);
+                }
+                Ok(None) => index += 1,
+                Err(e) => {
+                    let job = self.jobs.remove(index);
+                    error!(
+                        self.logger,
+                        "Failed to check on job {}, forgetting it: '{}'.", job.id, e
+                    );
+                }
+            }
+        }
+    }
+
+    pub(super) fn handle_jobs_command(&mut self, _args: &[&str]) -> i32 {
+        self.reap_jobs();
+        for job in &self.jobs {
+            let _ = writeln!(
+                self.output,
+                "[{}] {:<8} {}",
+                job.id, "Running", job.command_line
+            );
+        }
+        0
+    }
+
+    pub(super) fn handle_wait_command(&mut self, args: &[&str]) -> i32 {
+        let mut ids = Vec::new();
+        for arg in args {
+            match arg.trim_start_matches('%').parse::<usize>() {
+                Ok(id) => ids.push(id),
+                Err(_) => {
+                    error!(
+                        self.logger,
+                        "'wait' command requires job ids [wait [job...]], got '{}'.", arg
+                    );
+                    return 1;
+                }
+            }
+        }
+        if ids.is_empty() {
+            ids = self.jobs.iter().map(|job| job.id).collect();
+        }
+
+        let mut status = 0;
+        for id in ids {
+            let Some(index) = self.jobs.iter().position(|job| job.id == id) else {
+                error!(self.logger, "Job {} doesn't exist.", id);
+                status = 1;
+                continue;
+            };
+
+            let mut job = self.jobs.remove(index);
+            match job.child.wait() {
+                Ok(exit_status) => {
+                    self.report_finished_job(&job, exit_status);
+                    status = exit_status_code(exit_status);
+                }
+                Err(e) => {
+                    error!(self.logger, "Failed to wait for job {}: '{}'.", id, e);
+                    status = 1;
+                }
+            }
+        }
+
+        status
+    }
+
+    fn report_finished_job(&mut self, job: &Job, status: ExitStatus) {
+        let state = match exit_status_code(status) {
+            0 => String::from("Done"),
+            code => format!("Exit {}", code),
+        };
+        let _ = writeln!(
+            self.output,
+            "[{}] {:<8} {}",
+            job.id, state, job.command_line
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{thread, time::Duration};
+
+    use super::super::test_support::TestShell;
+
+    #[test]
+    fn background_jobs_are_tracked_then_reaped() {
+        let mut shell = TestShell::new();
+
+        assert_eq!(shell.run("sleep 0.2 &"), 0);
+        assert!(shell.take_output().starts_with("[1] "));
+        assert_eq!(shell.run_output("jobs"), "[1] Running  sleep 0.2\n");
+
+        for _ in 0..50 {
+            shell.mbash.reap_jobs();
+            if shell.mbash.jobs.is_empty() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(50));
+        }
+        assert!(shell.mbash.jobs.is_empty());
+        assert_eq!(shell.take_output(), "[1] Done     sleep 0.2\n");
+        assert_eq!(shell.run_output("jobs"), "");
+    }
+
+    #[test]
+    fn wait_returns_the_job_status() {
+        let mut shell = TestShell::new();
+        shell.run("sh -c 'exit 3' &");
+        shell.take_output();
+
+        assert_eq!(shell.run("wait %1"), 3);
+        assert_eq!(shell.take_output(), "[1] Exit 3   sh -c 'exit 3'\n");
+        assert_eq!(shell.run("wait 1"), 1);
+        assert!(shell.logged("Job 1 doesn't exist."));
+    }
+
+    #[test]
+    fn builtins_cannot_run_in_the_background() {
+        let mut shell = TestShell::new();
+
+        assert_eq!(shell.run("ls &"), 1);
+        assert!(shell.logged("only external commands can run in the background"));
+        assert!(shell.mbash.jobs.is_empty());
+    }
+}
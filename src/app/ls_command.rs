@@ -0,0 +1,707 @@
+use logger::{debug, error};
+use serde_json::{Value, json};
+use std::{
+    collections::HashSet,
+    env,
+    ffi::OsString,
+    io::{self, IsTerminal, Write},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use super::{Mbash, arg_spec::ArgSpec};
+use crate::{file_system::FileMetadata, helper_functions};
+
+/// Setting this variable to anything turns colored output off, see https://no-color.org
+const NO_COLOR_ENV_VAR: &str = "NO_COLOR";
+const DIRECTORY_COLOR: &str = "\x1b[34m";
+const EXECUTABLE_COLOR: &str = "\x1b[32m";
+const SYMLINK_COLOR: &str = "\x1b[36m";
+const RESET_COLOR: &str = "\x1b[0m";
+const LS_ARGS: ArgSpec = ArgSpec {
+    name: "ls",
+    flags: &[
+        &["-a", "--all"],
+        &["-l"],
+        &["-R", "--recursive"],
+        &["--group-dirs"],
+        &["--json"],
+        &["--no-cache"],
+    ],
+    operand: "directory",
+    min_operands: 0,
+    max_operands: Some(1),
+};
+
+#[derive(Default)]
+struct ListOptions {
+    show_all: bool,
+    long_format: bool,
+    group_directories: bool,
+    json: bool,
+    recursive: bool,
+    no_cache: bool,
+    color: bool,
+}
+
+/// Directories modified more recently than this aren't cached, since a change within the same
+/// tick of the file system's timestamps wouldn't update their modified time
+const LISTING_CACHE_SETTLE_TIME: Duration = Duration::from_secs(2);
+
+/// The entries of a directory as they were when its modified time was `modified`.
+/// Adding, removing or renaming an entry updates the directory's modified time, which makes
+/// the listing stale.
+pub(super) struct CachedListing {
+    modified: SystemTime,
+    entries: Vec<ListedEntry>,
+}
+
+/// An entry as it was when its directory was read, kept so cached listings don't need to read
+/// it again. Only its kind is relied on, sizes and times are read fresh when they're shown.
+#[derive(Clone)]
+struct ListedEntry {
+    name: OsString,
+    path: PathBuf,
+    metadata: Option<FileMetadata>,
+}
+
+impl Mbash {
+    pub(super) fn handle_ls_command(&mut self, args: &[&str]) -> i32 {
+        let Some(args) = self.parse_args(&LS_ARGS, args) else {
+            return 1;
+        };
+        let mut options = ListOptions {
+            show_all: args.has("-a"),
+            long_format: args.has("-l"),
+            group_directories: args.has("--group-dirs"),
+            json: args.has("--json"),
+            recursive: args.has("-R"),
+            no_cache: args.has("--no-cache"),
+            ..ListOptions::default()
+        };
+
+        if options.recursive && options.json {
+            error!(self.logger, "'ls' command can't combine -R with --json.");
+            return 1;
+        }
+
+        // Colors are only for a terminal, piped or redirected output keeps the plain markers
+        options.color = self.output.is_stdout()
+            && io::stdout().is_terminal()
+            && env::var_os(NO_COLOR_ENV_VAR).is_none();
+
+        let directory = match args.operands.first() {
+            Some(target) => self.resolve_path(target),
+            None => self.current_path.clone(),
+        };
+
+        self.list_files(&directory, &options)
+    }
+
+    fn list_files(&mut self, directory: &Path, options: &ListOptions) -> i32 {
+        let mut listed_directories = HashSet::new();
+        if let Ok(directory) = self.file_system.canonicalize(directory) {
+            listed_directories.insert(directory);
+        }
+        self.list_directory(directory, options, &mut listed_directories)
+    }
+
+    /// Lists a directory, then with -R each of its subdirectories under a header.
+    /// Directories are remembered by their canonical path so symlink cycles are listed only once.
+    fn list_directory(
+        &mut self,
+        directory: &Path,
+        options: &ListOptions,
+        listed_directories: &mut HashSet<PathBuf>,
+    ) -> i32 {
+        let (mut sorted_entries, complete) = match self.read_listing(directory, !options.no_cache) {
+            Ok(listing) => listing,
+            Err(e) if e.kind() == io::ErrorKind::NotFound && directory == self.current_path => {
+                error!(
+                    self.logger,
+                    "The current directory '{}' no longer exists.",
+                    directory.display()
+                );
+                if !self.recover_current_path() {
+                    return 1;
+                }
+
+                let directory = self.current_path.clone();
+                return self.list_directory(&directory, options, listed_directories);
+            }
+            Err(e) => {
+                error!(
+                    self.logger,
+                    "Failed to read directory '{}': '{}'.",
+                    directory.display(),
+                    e
+                );
+                return 1;
+            }
+        };
+
+        let mut status = if complete { 0 } else { 1 };
+        if options.group_directories {
+            sorted_entries.sort_by(|a, b| {
+                let is_directory =
+                    |entry: &ListedEntry| entry.metadata.is_some_and(|metadata| metadata.is_dir());
+                (!is_directory(a), &a.name).cmp(&(!is_directory(b), &b.name))
+            });
+        } else {
+            sorted_entries.sort_by(|a, b| a.name.cmp(&b.name));
+        }
+
+        let mut json_entries = Vec::new();
+        let mut subdirectories = Vec::new();
+        for entry in sorted_entries {
+            let name = entry.name.to_string_lossy().into_owned();
+            let path = entry.path.clone();
+            let relative_path = path.strip_prefix(&self.current_path).unwrap_or(&path);
+            let ignored = self.is_ignored(&relative_path.to_string_lossy());
+            if !options.show_all && (name.starts_with('.') || ignored) {
+                continue;
+            }
+
+            let is_directory = self
+                .file_system
+                .metadata(&path)
+                .is_ok_and(|metadata| metadata.is_dir());
+            if options.recursive && !ignored && is_directory {
+                subdirectories.push(path.clone());
+            }
+
+            if options.json {
+                let metadata = self.file_system.symlink_metadata(&entry.path).ok();
+                json_entries.push(json_entry(metadata, name));
+                continue;
+            }
+
+            // Permissions can change without touching the directory, so they're read fresh
+            let color = match entry.metadata {
+                Some(_) if options.color => self
+                    .file_system
+                    .symlink_metadata(&entry.path)
+                    .ok()
+                    .and_then(entry_color),
+                _ => None,
+            };
+            let mut shown_name = match color {
+                Some(color) => format!("{}{}{}", color, name, RESET_COLOR),
+                None => name.clone(),
+            };
+            if entry.metadata.is_some_and(|metadata| metadata.is_symlink()) {
+                shown_name.push_str(&self.describe_link_target(&entry));
+            }
+
+            if options.long_format {
+                let long_entry = self.format_long_entry(&entry, &shown_name);
+                let _ = writeln!(self.output, "{}", long_entry);
+                continue;
+            }
+
+            if color.is_some() {
+                let _ = writeln!(self.output, "{}", shown_name);
+                continue;
+            }
+
+            let _ = match entry.metadata {
+                Some(metadata) if metadata.is_dir() => writeln!(self.output, "[DIR] {}", name),
+                Some(metadata) if metadata.is_symlink() => {
+                    writeln!(self.output, "[LINK] {}", shown_name)
+                }
+                Some(_) => writeln!(self.output, "{}", name),
+                None => writeln!(self.output, "[?] {}", name),
+            };
+        }
+
+        if options.json {
+            let _ = writeln!(self.output, "{}", Value::Array(json_entries));
+        }
+
+        for subdirectory in subdirectories {
+            let canonical_path = self
+                .file_system
+                .canonicalize(&subdirectory)
+                .unwrap_or(subdirectory.clone());
+            if !listed_directories.insert(canonical_path) {
+                debug!(
+                    self.logger,
+                    "Skipping '{}', it has already been listed.",
+                    subdirectory.display()
+                );
+                continue;
+            }
+
+            let header = subdirectory
+                .strip_prefix(&self.current_path)
+                .unwrap_or(&subdirectory);
+            let _ = writeln!(self.output, "\n{}:", header.display());
+            if self.list_directory(&subdirectory, options, listed_directories) != 0 {
+                status = 1;
+            }
+        }
+
+        status
+    }
+
+    /// Reads the entries of a directory, reusing the cached ones while the directory's modified
+    /// time is unchanged. Nothing is cached until `set lscache on`. Also returns whether every entry could be read; partial listings
+    /// aren't cached.
+    fn read_listing(
+        &mut self,
+        directory: &Path,
+        use_cache: bool,
+    ) -> io::Result<(Vec<ListedEntry>, bool)> {
+        let modified = self.file_system.metadata(directory)?.modified;
+        if use_cache
+            && let Some(cached) = self.listing_cache.get(directory)
+            && Some(cached.modified) == modified
+        {
+            debug!(
+                self.logger,
+                "Reusing the cached listing of '{}'.",
+                directory.display()
+            );
+            return Ok((cached.entries.clone(), true));
+        }
+
+        let mut entries = Vec::new();
+        let mut complete = true;
+        for entry in self.file_system.read_dir(directory)? {
+            match entry {
+                Ok(path) => entries.push(ListedEntry {
+                    name: path.file_name().unwrap_or_default().to_os_string(),
+                    metadata: self.file_system.symlink_metadata(&path).ok(),
+                    path,
+                }),
+                Err(e) => {
+                    error!(self.logger, "Failed to read directory entry: '{}'.", e);
+                    complete = false;
+                }
+            }
+        }
+
+        let settled = |modified: SystemTime| {
+            SystemTime::now()
+                .duration_since(modified)
+                .is_ok_and(|age| age >= LISTING_CACHE_SETTLE_TIME)
+        };
+        match modified {
+            Some(modified) if self.cache_listings && complete && settled(modified) => {
+                let listing = CachedListing {
+                    modified,
+                    entries: entries.clone(),
+                };
+                self.listing_cache.insert(directory.to_path_buf(), listing);
+            }
+            _ => {
+                self.listing_cache.remove(directory);
+            }
+        }
+        Ok((entries, complete))
+    }
+
+    /// Describes where a symlink points as ` -> <target>`, without following it, so broken
+    /// links are listed like any other entry and marked as such
+    fn describe_link_target(&self, entry: &ListedEntry) -> String {
+        let path = &entry.path;
+        match self.file_system.read_link(path) {
+            Ok(target) if self.file_system.metadata(path).is_ok() => {
+                format!(" -> {}", target.display())
+            }
+            Ok(target) => format!(" -> {} (broken)", target.display()),
+            Err(e) => {
+                debug!(
+                    self.logger,
+                    "Failed to read the target of '{}': '{}'.",
+                    path.display(),
+                    e
+                );
+                String::from(" -> ?")
+            }
+        }
+    }
+
+    /// Formats an entry as `<type> <size> <modified> <name>`, using `?` for unreadable metadata
+    fn format_long_entry(&self, entry: &ListedEntry, name: &str) -> String {
+        let metadata = match self.file_system.symlink_metadata(&entry.path) {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                debug!(
+                    self.logger,
+                    "Failed to read metadata of '{}': '{}'.", name, e
+                );
+                return format!("? {:>7} {:>19} {}", "?", "?", name);
+            }
+        };
+
+        let type_indicator = if metadata.is_dir() {
+            'd'
+        } else if metadata.is_symlink() {
+            'l'
+        } else {
+            '-'
+        };
+
+        let modified = match metadata.modified {
+            Some(modified) => helper_functions::format_timestamp(modified),
+            None => {
+                debug!(self.logger, "No modified time for '{}'.", name);
+                String::from("?")
+            }
+        };
+
+        format!(
+            "{} {:>7} {:>19} {}",
+            type_indicator,
+            helper_functions::human_readable_size(metadata.len),
+            modified,
+            name
+        )
+    }
+}
+
+/// Picks the color of an entry's name, leaving regular files uncolored
+fn entry_color(metadata: FileMetadata) -> Option<&'static str> {
+    if metadata.is_symlink() {
+        Some(SYMLINK_COLOR)
+    } else if metadata.is_dir() {
+        Some(DIRECTORY_COLOR)
+    } else if metadata.executable {
+        Some(EXECUTABLE_COLOR)
+    } else {
+        None
+    }
+}
+
+/// Describes an entry as `{"name", "type", "size"}`, with a `null` size when metadata is unreadable
+fn json_entry(metadata: Option<FileMetadata>, name: String) -> Value {
+    let entry_type = match &metadata {
+        Some(metadata) if metadata.is_dir() => "directory",
+        Some(metadata) if metadata.is_symlink() => "symlink",
+        Some(_) => "file",
+        None => "unknown",
+    };
+
+    json!({
+        "name": name,
+        "type": entry_type,
+        "size": metadata.map(|metadata| metadata.len),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+    use std::{
+        fs::{self, File},
+        time::{Duration, SystemTime},
+    };
+
+    use super::super::test_support::{TestShell, lock_process};
+    use super::{DIRECTORY_COLOR, ListOptions, RESET_COLOR};
+
+    #[test]
+    fn ls_hides_ignored_entries_unless_all() {
+        let mut shell = TestShell::new();
+        shell.write(".mignoring", "*.tmp\n");
+        shell.write("kept.txt", "");
+        shell.write("scratch.tmp", "");
+        shell.mbash.refresh_workspace().unwrap();
+
+        assert_eq!(shell.run_output("ls"), "kept.txt\n");
+        assert!(shell.run_output("ls -a").contains("scratch.tmp\n"));
+    }
+
+    #[test]
+    fn ls_shows_dotfiles_only_with_all() {
+        let mut shell = TestShell::new();
+        shell.write("visible", "");
+        shell.write(".hidden", "");
+
+        assert_eq!(shell.run_output("ls"), "visible\n");
+        assert_eq!(shell.run_output("ls -a"), ".hidden\nvisible\n");
+        assert_eq!(shell.run_output("ls --all"), ".hidden\nvisible\n");
+    }
+
+    #[test]
+    fn ls_marks_directories() {
+        let mut shell = TestShell::new();
+        shell.run("mkdir sub");
+
+        assert_eq!(shell.run_output("ls"), "[DIR] sub\n");
+    }
+
+    #[test]
+    fn ls_long_format_shows_size_and_type() {
+        let mut shell = TestShell::new();
+        shell.write("notes.txt", "hello");
+        shell.run("mkdir sub");
+
+        let output = shell.run_output("ls -l");
+        let rows: Vec<Vec<&str>> = output
+            .lines()
+            .map(|line| line.split_whitespace().collect())
+            .collect();
+        assert_eq!(rows.len(), 2);
+        assert!(rows.iter().all(|columns| columns.len() == 4));
+        assert_eq!(rows[0][0], "-");
+        assert_eq!(rows[0][1], "5");
+        assert_eq!(rows[0][3], "notes.txt");
+        assert_eq!(rows[1][0], "d");
+        assert_eq!(rows[1][3], "sub");
+    }
+
+    #[test]
+    fn ls_sorts_entries_by_name() {
+        let mut shell = TestShell::new();
+        for name in ["c.txt", "a.txt", "b.txt"] {
+            shell.write(name, "");
+        }
+
+        assert_eq!(shell.run_output("ls"), "a.txt\nb.txt\nc.txt\n");
+    }
+
+    #[test]
+    fn ls_group_dirs_lists_directories_first() {
+        let mut shell = TestShell::new();
+        shell.write("a.txt", "");
+        shell.run("mkdir z");
+
+        assert_eq!(shell.run_output("ls"), "a.txt\n[DIR] z\n");
+        assert_eq!(shell.run_output("ls --group-dirs"), "[DIR] z\na.txt\n");
+    }
+
+    #[test]
+    fn ls_json_describes_each_entry() {
+        let mut shell = TestShell::new();
+        shell.write("notes.txt", "hello");
+        shell.run("mkdir sub");
+
+        let output = shell.run_output("ls --json");
+        let entries: Value = serde_json::from_str(&output).unwrap();
+        let entries = entries.as_array().unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0]["name"], "notes.txt");
+        assert_eq!(entries[0]["type"], "file");
+        assert_eq!(entries[0]["size"], 5);
+        assert_eq!(entries[1]["name"], "sub");
+        assert_eq!(entries[1]["type"], "directory");
+    }
+
+    #[test]
+    fn ls_falls_back_when_the_current_directory_is_removed() {
+        let _guard = lock_process();
+        let mut shell = TestShell::new();
+        shell.write("kept.txt", "");
+        let parent = shell.mbash.current_path.clone();
+        shell.run("mkdir doomed");
+        assert_eq!(shell.run("cd doomed"), 0);
+        fs::remove_dir(shell.path("")).unwrap();
+
+        assert_eq!(shell.run_output("ls"), "kept.txt\n");
+        assert_eq!(shell.mbash.current_path, parent);
+        assert!(shell.logged("no longer exists"));
+        assert_eq!(shell.run("ls"), 0);
+    }
+
+    #[test]
+    fn ls_leaves_out_colors_when_not_writing_to_a_terminal() {
+        let mut shell = TestShell::new();
+        shell.run("mkdir sub");
+        shell.write("notes.txt", "");
+
+        let output = shell.run_output("ls");
+        assert!(!output.contains('\x1b'));
+        assert_eq!(output, "notes.txt\n[DIR] sub\n");
+    }
+
+    #[test]
+    fn colored_listings_drop_the_markers() {
+        let mut shell = TestShell::new();
+        shell.run("mkdir sub");
+        shell.write("notes.txt", "");
+        let options = ListOptions {
+            color: true,
+            ..ListOptions::default()
+        };
+
+        let directory = shell.mbash.current_path.clone();
+        assert_eq!(shell.mbash.list_files(&directory, &options), 0);
+        assert_eq!(
+            shell.take_output(),
+            format!("notes.txt\n{}sub{}\n", DIRECTORY_COLOR, RESET_COLOR)
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn executables_are_colored_green() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let mut shell = TestShell::new();
+        shell.write("run.sh", "");
+        fs::set_permissions(shell.path("run.sh"), fs::Permissions::from_mode(0o755)).unwrap();
+        let options = ListOptions {
+            color: true,
+            ..ListOptions::default()
+        };
+
+        let directory = shell.mbash.current_path.clone();
+        shell.mbash.list_files(&directory, &options);
+        assert_eq!(
+            shell.take_output(),
+            format!("{}run.sh{}\n", super::EXECUTABLE_COLOR, RESET_COLOR)
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn ls_shows_where_symlinks_point() {
+        let mut shell = TestShell::new();
+        shell.write("target.txt", "");
+        std::os::unix::fs::symlink("target.txt", shell.path("link")).unwrap();
+
+        assert_eq!(
+            shell.run_output("ls"),
+            "[LINK] link -> target.txt\ntarget.txt\n"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn ls_marks_broken_symlinks() {
+        let mut shell = TestShell::new();
+        std::os::unix::fs::symlink("missing.txt", shell.path("dangling")).unwrap();
+
+        assert_eq!(shell.run("ls"), 0);
+        assert_eq!(
+            shell.take_output(),
+            "[LINK] dangling -> missing.txt (broken)\n"
+        );
+        assert!(shell.run_output("ls -l").starts_with("l "));
+    }
+
+    #[test]
+    fn ls_recursive_lists_subdirectories_under_headers() {
+        let mut shell = TestShell::new();
+        shell.write("top.txt", "");
+        shell.write("sub/inner.txt", "");
+        shell.write("sub/deeper/leaf.txt", "");
+
+        assert_eq!(
+            shell.run_output("ls -R"),
+            "[DIR] sub\ntop.txt\n\nsub:\n[DIR] deeper\ninner.txt\n\nsub/deeper:\nleaf.txt\n"
+        );
+    }
+
+    #[test]
+    fn ls_recursive_prunes_ignored_directories() {
+        let mut shell = TestShell::new();
+        shell.write(".mignoring", "build\n");
+        shell.write("build/out.o", "");
+        shell.write("src/main.rs", "");
+        shell.mbash.refresh_workspace().unwrap();
+
+        assert_eq!(shell.run_output("ls -R"), "[DIR] src\n\nsrc:\nmain.rs\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn ls_recursive_lists_symlink_cycles_once() {
+        let mut shell = TestShell::new();
+        shell.write("sub/file.txt", "");
+        std::os::unix::fs::symlink("..", shell.path("sub/up")).unwrap();
+
+        assert_eq!(shell.run("ls -R"), 0);
+        assert!(shell.logged("it has already been listed"));
+    }
+
+    fn reused_listings(shell: &TestShell) -> usize {
+        shell
+            .logs()
+            .iter()
+            .filter(|message| message.contains("Reusing the cached listing"))
+            .count()
+    }
+
+    /// Backdates the shell directory's modified time so its listing can be cached
+    fn set_directory_age(shell: &TestShell, age: Duration) {
+        let directory = File::open(shell.path("")).unwrap();
+        directory.set_modified(SystemTime::now() - age).unwrap();
+    }
+
+    #[test]
+    fn listings_are_not_cached_by_default() {
+        let mut shell = TestShell::new();
+        shell.write("a.txt", "");
+        set_directory_age(&shell, Duration::from_secs(3600));
+
+        shell.run_output("ls");
+        assert_eq!(shell.run_output("ls"), "a.txt\n");
+        assert_eq!(reused_listings(&shell), 0);
+    }
+
+    #[test]
+    fn repeated_ls_reuses_the_cached_listing() {
+        let mut shell = TestShell::new();
+        shell.run("set lscache on");
+        shell.write("a.txt", "");
+        set_directory_age(&shell, Duration::from_secs(3600));
+
+        let first = shell.run_output("ls");
+        assert_eq!(reused_listings(&shell), 0);
+        assert_eq!(shell.run_output("ls"), first);
+        assert_eq!(reused_listings(&shell), 1);
+    }
+
+    #[test]
+    fn adding_a_file_invalidates_the_cached_listing() {
+        let mut shell = TestShell::new();
+        shell.run("set lscache on");
+        shell.write("a.txt", "");
+        set_directory_age(&shell, Duration::from_secs(3600));
+        shell.run_output("ls");
+
+        shell.write("b.txt", "");
+        set_directory_age(&shell, Duration::from_secs(1800));
+        assert_eq!(shell.run_output("ls"), "a.txt\nb.txt\n");
+        assert_eq!(reused_listings(&shell), 0);
+    }
+
+    #[test]
+    fn recently_modified_directories_are_not_cached() {
+        let mut shell = TestShell::new();
+        shell.run("set lscache on");
+        shell.write("a.txt", "");
+        set_directory_age(&shell, Duration::ZERO);
+        shell.run_output("ls");
+        assert!(shell.mbash.listing_cache.is_empty());
+
+        shell.write("b.txt", "");
+        set_directory_age(&shell, Duration::ZERO);
+        assert_eq!(shell.run_output("ls"), "a.txt\nb.txt\n");
+        assert_eq!(reused_listings(&shell), 0);
+    }
+
+    #[test]
+    fn no_cache_reads_the_directory_again() {
+        let mut shell = TestShell::new();
+        shell.run("set lscache on");
+        shell.write("a.txt", "");
+        set_directory_age(&shell, Duration::from_secs(3600));
+        shell.run_output("ls");
+
+        assert_eq!(shell.run_output("ls --no-cache"), "a.txt\n");
+        assert_eq!(reused_listings(&shell), 0);
+    }
+
+    #[test]
+    fn lscache_takes_only_on_or_off() {
+        let mut shell = TestShell::new();
+
+        assert_eq!(shell.run("set lscache maybe"), 1);
+        assert!(shell.logged("'set lscache' requires on or off"));
+        assert!(!shell.mbash.cache_listings);
+    }
+}
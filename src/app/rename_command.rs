@@ -0,0 +1,206 @@
+use logger::{debug, error};
+use regex::Regex;
+use std::{fs, io::Write, path::Path};
+
+use super::{Mbash, arg_spec::ArgSpec};
+
+const RENAME_ARGS: ArgSpec = ArgSpec {
+    name: "rename",
+    flags: &[&["-n"], &["-f"]],
+    operand: "argument",
+    min_operands: 3,
+    max_operands: None,
+};
+
+impl Mbash {
+    pub(super) fn handle_rename_command(&mut self, args: &[&str]) -> i32 {
+        let Some(args) = self.parse_args(&RENAME_ARGS, args) else {
+            return 1;
+        };
+        let (preview, force) = (args.has("-n"), args.has("-f"));
+        let [raw_pattern, replacement, file_names @ ..] = args.operands.as_slice() else {
+            unreachable!("the argument spec requires a pattern, a replacement and files");
+        };
+
+        let pattern = match Regex::new(raw_pattern) {
+            Ok(pattern) => pattern,
+            Err(e) => {
+                error!(
+                    self.logger,
+                    "'{}' isn't a valid regular expression: '{}'.", raw_pattern, e
+                );
+                return 1;
+            }
+        };
+
+        let mut status = 0;
+        for file_name in file_names {
+            if self.rename_file(file_name, &pattern, replacement, preview, force) != 0 {
+                status = 1;
+            }
+        }
+
+        status
+    }
+
+    /// Replaces the first match of `pattern` in the last component of `file_name`, where the
+    /// replacement can refer to capture groups as `$1`. With `preview` the change is only printed.
+    fn rename_file(
+        &mut self,
+        file_name: &str,
+        pattern: &Regex,
+        replacement: &str,
+        preview: bool,
+        force: bool,
+    ) -> i32 {
+        let source = self.resolve_path(file_name);
+        let Some(old_name) = source
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+        else {
+            error!(self.logger, "'{}' has no name to rename.", file_name);
+            return 1;
+        };
+
+        let new_name = pattern.replace(&old_name, replacement);
+        if new_name == old_name {
+            debug!(
+                self.logger,
+                "'{}' doesn't match the pattern, leaving it as is.", file_name
+            );
+            return 0;
+        }
+        if new_name.is_empty() || new_name.contains(['/', '\\']) {
+            error!(
+                self.logger,
+                "Renaming '{}' to '{}' would move it out of its directory.", file_name, new_name
+            );
+            return 1;
+        }
+
+        if !source.exists() {
+            error!(self.logger, "'{}' does not exist.", source.display());
+            return 1;
+        }
+
+        let destination = source.with_file_name(new_name.as_ref());
+        if destination.exists() && !force {
+            error!(
+                self.logger,
+                "'{}' already exists, use -f to overwrite it.",
+                destination.display()
+            );
+            return 1;
+        }
+
+        let shown_destination = Path::new(file_name).with_file_name(new_name.as_ref());
+        if preview {
+            let _ = writeln!(
+                self.output,
+                "{} -> {}",
+                file_name,
+                shown_destination.display()
+            );
+            return 0;
+        }
+
+        if self.skip_for_dry_run(&format!(
+            "rename '{}' to '{}'",
+            file_name,
+            shown_destination.display()
+        )) {
+            return 0;
+        }
+
+        match fs::rename(&source, &destination) {
+            Ok(()) => {
+                debug!(
+                    self.logger,
+                    "Renamed '{}' to '{}'.",
+                    file_name,
+                    shown_destination.display()
+                );
+                0
+            }
+            Err(e) => {
+                error!(
+                    self.logger,
+                    "Failed to rename '{}' to '{}': '{}'.",
+                    file_name,
+                    shown_destination.display(),
+                    e
+                );
+                1
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_support::TestShell;
+
+    #[test]
+    fn rename_swaps_extensions() {
+        let mut shell = TestShell::new();
+        shell.write("a.txt", "a");
+        shell.write("b.txt", "b");
+
+        assert_eq!(shell.run(r"rename '\.txt$' .md *.txt"), 0);
+        assert!(!shell.path("a.txt").exists());
+        assert_eq!(shell.read("a.md"), "a");
+        assert_eq!(shell.read("b.md"), "b");
+    }
+
+    #[test]
+    fn rename_substitutes_capture_groups() {
+        let mut shell = TestShell::new();
+        shell.write("report-2024.txt", "");
+
+        assert_eq!(
+            shell.run(r"rename '(\w+)-(\d+)' '${2}_$1' report-2024.txt"),
+            0
+        );
+        assert!(shell.path("2024_report.txt").is_file());
+    }
+
+    #[test]
+    fn rename_preview_changes_nothing() {
+        let mut shell = TestShell::new();
+        shell.write("a.txt", "");
+
+        assert_eq!(
+            shell.run_output("rename -n .txt .md a.txt"),
+            "a.txt -> a.md\n"
+        );
+        assert!(shell.path("a.txt").is_file());
+        assert!(!shell.path("a.md").exists());
+    }
+
+    #[test]
+    fn rename_refuses_to_overwrite_without_force() {
+        let mut shell = TestShell::new();
+        shell.write("a.txt", "new");
+        shell.write("a.md", "old");
+
+        assert_eq!(shell.run("rename .txt .md a.txt"), 1);
+        assert!(shell.logged("already exists, use -f to overwrite it"));
+        assert_eq!(shell.read("a.md"), "old");
+
+        assert_eq!(shell.run("rename -f .txt .md a.txt"), 0);
+        assert_eq!(shell.read("a.md"), "new");
+    }
+
+    #[test]
+    fn dry_run_rename_changes_nothing() {
+        let mut shell = TestShell::new();
+        shell.write("a.txt", "");
+        shell.run("set dryrun on");
+
+        assert_eq!(
+            shell.run_output("rename .txt .md a.txt"),
+            "dry run: would rename 'a.txt' to 'a.md'\n"
+        );
+        assert!(shell.path("a.txt").is_file());
+    }
+}
@@ -0,0 +1,1060 @@
+use logger::{debug, error};
+use std::{
+    collections::{BTreeMap, HashSet},
+    env,
+    fs::OpenOptions,
+    io::Write,
+    path::PathBuf,
+    time::Instant,
+};
+
+use super::{Mbash, PLUGIN_PREFIX, escape_history_entry};
+use crate::{
+    helper_functions,
+    parser::{self, Token},
+};
+
+/// Clears the screen and moves the cursor to the top-left corner
+const CLEAR_SCREEN_SEQUENCE: &str = "\x1b[2J\x1b[H";
+
+impl Mbash {
+    pub(super) fn handle_echo_command(&mut self, args: &[&str]) -> i32 {
+        let mut trailing_newline = true;
+        let mut interpret_escapes = false;
+
+        let mut words = args;
+        while let Some((&flag, rest)) = words.split_first() {
+            match flag {
+                "-n" => trailing_newline = false,
+                "-e" => interpret_escapes = true,
+                "-ne" | "-en" => {
+                    trailing_newline = false;
+                    interpret_escapes = true;
+                }
+                _ => break,
+            }
+            words = rest;
+        }
+
+        let mut output = words.join(" ");
+        if interpret_escapes {
+            output = interpret_escape_sequences(&output);
+        }
+
+        if trailing_newline {
+            let _ = writeln!(self.output, "{}", output);
+        } else {
+            let _ = write!(self.output, "{}", output);
+        }
+
+        0
+    }
+
+    pub(super) fn handle_clear_command(&mut self, _args: &[&str]) -> i32 {
+        // Older Windows consoles print ANSI sequences literally, so let the console clear itself
+        #[cfg(windows)]
+        if self.output.is_stdout() {
+            let clear_result = std::process::Command::new("cmd")
+                .args(["/C", "cls"])
+                .status();
+            match clear_result {
+                Ok(status) if status.success() => return 0,
+                Ok(_) | Err(_) => debug!(self.logger, "'cls' failed, falling back to ANSI."),
+            }
+        }
+
+        let clear_result = self
+            .output
+            .write_all(CLEAR_SCREEN_SEQUENCE.as_bytes())
+            .and_then(|_| self.output.flush());
+
+        match clear_result {
+            Ok(()) => 0,
+            Err(e) => {
+                error!(self.logger, "Failed to clear the screen: '{}'.", e);
+                1
+            }
+        }
+    }
+
+    pub(super) fn handle_history_command(&mut self, args: &[&str]) -> i32 {
+        let count = match args.first() {
+            Some(count) => match count.parse::<usize>() {
+                Ok(count) => count,
+                Err(e) => {
+                    error!(
+                        self.logger,
+                        "'history' expects a number of entries [history [count]], got '{}': {}.",
+                        count,
+                        e
+                    );
+                    return 1;
+                }
+            },
+            None => self.history.len(),
+        };
+
+        let skipped = self.history.len().saturating_sub(count);
+        for (index, line) in self.history.iter().enumerate().skip(skipped) {
+            let _ = writeln!(self.output, "{:>5}  {}", index + 1, line);
+        }
+
+        0
+    }
+
+    /// Appends a line to the history, dropping the oldest entries past `history_limit`
+    pub(super) fn record_history(&mut self, line: &str) {
+        if self.history_limit == 0 {
+            return;
+        }
+
+        self.history.push(line.to_string());
+        if self.history.len() > self.history_limit {
+            let overflow = self.history.len() - self.history_limit;
+            self.history.drain(..overflow);
+        }
+
+        let Some(history_file) = &self.history_file else {
+            return;
+        };
+        let append_result = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(history_file)
+            .and_then(|mut file| writeln!(file, "{}", escape_history_entry(line)));
+
+        if let Err(e) = append_result {
+            error!(
+                self.logger,
+                "Failed to append to '{}': '{}'.",
+                history_file.display(),
+                e
+            );
+        }
+    }
+
+    /// Replaces a leading `!!` or `!N` with the matching history entry, echoing the result.
+    /// Returns `None` if the referenced entry doesn't exist.
+    pub(super) fn expand_history(&mut self, command_line: &str) -> Option<String> {
+        let (designator, rest) = match command_line.split_once(char::is_whitespace) {
+            Some((designator, rest)) => (designator, Some(rest)),
+            None => (command_line, None),
+        };
+
+        let entry = if designator == "!!" {
+            match self.history.last() {
+                Some(entry) => entry,
+                None => {
+                    error!(self.logger, "'!!' used but the history is empty.");
+                    return None;
+                }
+            }
+        } else if let Some(index) = designator
+            .strip_prefix('!')
+            .and_then(|index| index.parse::<usize>().ok())
+        {
+            match index
+                .checked_sub(1)
+                .and_then(|index| self.history.get(index))
+            {
+                Some(entry) => entry,
+                None => {
+                    error!(
+                        self.logger,
+                        "'{}' is out of range, the history has {} entries.",
+                        designator,
+                        self.history.len()
+                    );
+                    return None;
+                }
+            }
+        } else {
+            return Some(command_line.to_string());
+        };
+
+        let expanded = match rest {
+            Some(rest) => format!("{} {}", entry, rest),
+            None => entry.clone(),
+        };
+        let _ = writeln!(self.output, "{}", expanded);
+        Some(expanded)
+    }
+
+    pub(super) fn handle_alias_command(&mut self, args: &[&str]) -> i32 {
+        if args.is_empty() {
+            let mut aliases: Vec<(&String, &String)> = self.aliases.iter().collect();
+            aliases.sort();
+            for (name, value) in aliases {
+                let _ = writeln!(self.output, "alias {}='{}'", name, value);
+            }
+            return 0;
+        }
+
+        let mut status = 0;
+        for arg in args {
+            match arg.split_once('=') {
+                Some((name, value)) if parser::has_unquoted_operator(value) => {
+                    error!(
+                        self.logger,
+                        "Alias '{}' can't contain '|', '&', ';', '<' or '>' outside of quotes, got '{}'.",
+                        name,
+                        value
+                    );
+                    status = 1;
+                }
+                Some((name, value)) if !name.is_empty() => {
+                    debug!(self.logger, "Defined alias '{}' as '{}'.", name, value);
+                    self.aliases.insert(name.to_string(), value.to_string());
+                }
+                Some(_) => {
+                    error!(
+                        self.logger,
+                        "'alias' requires a name [alias <name>=<value>], got '{}'.", arg
+                    );
+                    status = 1;
+                }
+                None => match self.aliases.get(*arg) {
+                    Some(value) => {
+                        let _ = writeln!(self.output, "alias {}='{}'", arg, value);
+                    }
+                    None => {
+                        error!(self.logger, "Alias '{}' isn't defined.", arg);
+                        status = 1;
+                    }
+                },
+            }
+        }
+
+        status
+    }
+
+    pub(super) fn handle_unalias_command(&mut self, args: &[&str]) -> i32 {
+        if args.is_empty() {
+            error!(
+                self.logger,
+                "'unalias' command requires at least one name [unalias <name>...]."
+            );
+            return 1;
+        }
+
+        let mut status = 0;
+        for name in args {
+            if self.aliases.remove(*name).is_some() {
+                debug!(self.logger, "Removed alias '{}'.", name);
+            } else {
+                error!(self.logger, "Alias '{}' isn't defined.", name);
+                status = 1;
+            }
+        }
+
+        status
+    }
+
+    pub(super) fn handle_set_command(&mut self, args: &[&str]) -> i32 {
+        match args {
+            [] => {
+                let mut variables: BTreeMap<String, String> = env::vars().collect();
+                for (name, value) in &self.variables {
+                    match value {
+                        Some(value) => variables.insert(name.clone(), value.clone()),
+                        None => variables.remove(name),
+                    };
+                }
+                for (name, value) in variables {
+                    let _ = writeln!(self.output, "{}={}", name, value);
+                }
+                0
+            }
+            ["prefix", prefix] => {
+                if self.set_internal_command_prefix(prefix) {
+                    0
+                } else {
+                    1
+                }
+            }
+            ["dryrun", value] => {
+                let Some(dry_run) = self.parse_switch("dryrun", value) else {
+                    return 1;
+                };
+                self.dry_run = dry_run;
+                debug!(self.logger, "Turned dry-run mode {}.", value);
+                0
+            }
+            ["lscache", value] => {
+                let Some(cache_listings) = self.parse_switch("lscache", value) else {
+                    return 1;
+                };
+                self.cache_listings = cache_listings;
+                if !cache_listings {
+                    self.listing_cache.clear();
+                }
+                debug!(self.logger, "Turned the ls cache {}.", value);
+                0
+            }
+            _ => {
+                let mut status = 0;
+                for arg in args {
+                    match arg.split_once('=') {
+                        Some((name, value)) if parser::is_variable_name(name) => {
+                            debug!(self.logger, "Set variable '{}' to '{}'.", name, value);
+                            // Exported and inherited variables stay exported
+                            if self.variable(name).is_none()
+                                && !self.exported_variables.contains(name)
+                            {
+                                self.unexported_variables.insert(name.to_string());
+                            }
+                            self.variables
+                                .insert(name.to_string(), Some(value.to_string()));
+                        }
+                        _ => {
+                            error!(
+                                self.logger,
+                                "'set' command requires a variable assignment [set <name>=<value>...], got '{}'.",
+                                arg
+                            );
+                            status = 1;
+                        }
+                    }
+                }
+                status
+            }
+        }
+    }
+
+    /// Reads the on or off given to `set <setting>`
+    fn parse_switch(&self, setting: &str, value: &str) -> Option<bool> {
+        match value {
+            "on" => Some(true),
+            "off" => Some(false),
+            _ => {
+                error!(
+                    self.logger,
+                    "'set {0}' requires on or off [set {0} on|off], got '{1}'.", setting, value
+                );
+                None
+            }
+        }
+    }
+
+    /// The value of the shell variable `name`, falling back to the environment mbash started with
+    pub(super) fn variable(&self, name: &str) -> Option<String> {
+        match self.variables.get(name) {
+            Some(value) => value.clone(),
+            None => env::var(name).ok(),
+        }
+    }
+
+    pub(super) fn handle_export_command(&mut self, args: &[&str]) -> i32 {
+        if args.is_empty() {
+            let mut names: Vec<&String> = self.exported_variables.iter().collect();
+            names.sort();
+            for name in names {
+                let value = self.variable(name).unwrap_or_default();
+                let _ = writeln!(self.output, "export {}={}", name, value);
+            }
+            return 0;
+        }
+
+        let mut status = 0;
+        for arg in args {
+            let (name, value) = match arg.split_once('=') {
+                Some((name, value)) => (name, Some(value)),
+                None => (*arg, None),
+            };
+            if !parser::is_variable_name(name) {
+                error!(self.logger, "'{}' isn't a valid variable name.", name);
+                status = 1;
+                continue;
+            }
+
+            debug!(self.logger, "Exported variable '{}'.", name);
+            if let Some(value) = value {
+                self.variables
+                    .insert(name.to_string(), Some(value.to_string()));
+            }
+            self.unexported_variables.remove(name);
+            self.exported_variables.insert(name.to_string());
+        }
+
+        status
+    }
+
+    pub(super) fn handle_unset_command(&mut self, args: &[&str]) -> i32 {
+        if args.is_empty() {
+            error!(
+                self.logger,
+                "'unset' command requires at least one name [unset <name>...]."
+            );
+            return 1;
+        }
+
+        let mut status = 0;
+        for name in args {
+            if !parser::is_variable_name(name) {
+                error!(self.logger, "'{}' isn't a valid variable name.", name);
+                status = 1;
+                continue;
+            }
+
+            debug!(self.logger, "Removed variable '{}'.", name);
+            self.unexported_variables.remove(*name);
+            self.exported_variables.remove(*name);
+            self.variables.insert(name.to_string(), None);
+        }
+
+        status
+    }
+
+    pub(super) fn handle_version_command(&mut self, _args: &[&str]) -> i32 {
+        let _ = writeln!(
+            self.output,
+            "{} {}",
+            env!("CARGO_PKG_NAME"),
+            env!("CARGO_PKG_VERSION")
+        );
+        0
+    }
+
+    pub(super) fn handle_time_command(&mut self, args: &[&str]) -> i32 {
+        if args.is_empty() {
+            error!(
+                self.logger,
+                "'time' command requires a command to run [time <command>...]."
+            );
+            return 1;
+        }
+
+        let start = Instant::now();
+        self.execute_command(args.iter().map(|arg| arg.to_string()).collect());
+        let elapsed = start.elapsed();
+
+        let _ = writeln!(
+            self.output,
+            "real {}",
+            helper_functions::human_readable_duration(elapsed)
+        );
+        self.last_exit_code
+    }
+
+    pub(super) fn handle_source_command(&mut self, args: &[&str]) -> i32 {
+        let Some((file_name, _)) = args.split_first() else {
+            error!(
+                self.logger,
+                "'source' command requires a file [source <file>]."
+            );
+            return 1;
+        };
+
+        let path = self.resolve_path(file_name);
+        match self.run_script(&path) {
+            Ok(()) => {
+                debug!(self.logger, "Sourced '{}'.", path.display());
+                self.last_exit_code
+            }
+            Err(e) => {
+                error!(
+                    self.logger,
+                    "Failed to source '{}': '{}'.",
+                    path.display(),
+                    e
+                );
+                1
+            }
+        }
+    }
+
+    pub(super) fn handle_which_command(&mut self, args: &[&str]) -> i32 {
+        if args.is_empty() {
+            error!(
+                self.logger,
+                "'which' command requires at least one name [which <name>...]."
+            );
+            return 1;
+        }
+
+        let mut status = 0;
+        for name in args {
+            if self.commands.contains_key(*name) {
+                let _ = writeln!(self.output, "{}: mbash builtin", name);
+                continue;
+            }
+
+            match helper_functions::find_executable(name) {
+                Some(path) => {
+                    let _ = writeln!(self.output, "{}", path.display());
+                }
+                None => {
+                    error!(self.logger, "'{}' not found in PATH.", name);
+                    status = 1;
+                }
+            }
+        }
+
+        status
+    }
+
+    pub(super) fn handle_type_command(&mut self, args: &[&str]) -> i32 {
+        if args.is_empty() {
+            error!(
+                self.logger,
+                "'type' command requires at least one name [type <name>...]."
+            );
+            return 1;
+        }
+
+        // Checked in the order commands are dispatched, aliases first
+        let mut status = 0;
+        for name in args {
+            if let Some(value) = self.aliases.get(*name) {
+                let _ = writeln!(self.output, "{} is aliased to '{}'", name, value);
+            } else if self.commands.contains_key(*name) {
+                let _ = writeln!(self.output, "{} is an mbash builtin", name);
+            } else if let Some(plugin_path) = self.find_plugin(name) {
+                let _ = writeln!(
+                    self.output,
+                    "{} is an mbash plugin at {}",
+                    name,
+                    plugin_path.display()
+                );
+            } else if let Some(path) = helper_functions::find_executable(name) {
+                let _ = writeln!(self.output, "{} is {}", name, path.display());
+            } else {
+                error!(
+                    self.logger,
+                    "'{}' isn't an alias, a builtin or a command in PATH.", name
+                );
+                status = 1;
+            }
+        }
+
+        status
+    }
+
+    pub(super) fn handle_help_command(&mut self, args: &[&str]) -> i32 {
+        match args.first() {
+            Some(name) => match self.commands.get(*name) {
+                Some(command) => {
+                    let _ = writeln!(self.output, "usage: {}", command.usage);
+                    let _ = writeln!(self.output, "{}", command.description);
+                    0
+                }
+                None => match self.find_plugin(name) {
+                    Some(plugin_path) => {
+                        let _ = writeln!(
+                            self.output,
+                            "{} is a plugin run from {}",
+                            name,
+                            plugin_path.display()
+                        );
+                        0
+                    }
+                    None => {
+                        error!(self.logger, "'{}' isn't an mbash command.", name);
+                        1
+                    }
+                },
+            },
+            None => {
+                let mut names: Vec<&String> = self.commands.keys().collect();
+                names.sort();
+                for name in names {
+                    let _ = writeln!(
+                        self.output,
+                        "{:<10} {}",
+                        name, self.commands[name].description
+                    );
+                }
+
+                let plugins: Vec<(String, PathBuf)> =
+                    helper_functions::find_executables_with_prefix(PLUGIN_PREFIX)
+                        .into_iter()
+                        .filter(|(name, _)| !self.commands.contains_key(name))
+                        .collect();
+                if !plugins.is_empty() {
+                    let _ = writeln!(self.output, "\nPlugins:");
+                }
+                for (name, plugin_path) in plugins {
+                    let _ = writeln!(self.output, "{:<10} Runs {}.", name, plugin_path.display());
+                }
+                0
+            }
+        }
+    }
+
+    /// Replaces the first word with its alias expansion, expanding each alias at most once
+    /// so that self-referencing aliases like `ls="ls -a"` don't recurse forever.
+    pub(super) fn expand_aliases(&self, mut words: Vec<String>) -> Vec<String> {
+        let mut expanded_names = HashSet::new();
+
+        while let Some(value) = words.first().and_then(|name| self.aliases.get(name)) {
+            if !expanded_names.insert(words[0].clone()) {
+                break;
+            }
+
+            let alias_words = match parser::tokenize(
+                value,
+                self.last_exit_code,
+                &|name| self.variable(name),
+                &self.current_path,
+            ) {
+                Ok(alias_words) => alias_words,
+                Err(e) => {
+                    error!(self.logger, "Failed to expand alias '{}': {}.", words[0], e);
+                    break;
+                }
+            };
+
+            // Aliases with operators are refused when they're defined, so every token is a word
+            let alias_words = alias_words.into_iter().map(|token| match token {
+                Token::Word(word) => word,
+                Token::Operator(operator) => operator.to_string(),
+            });
+            words.splice(..1, alias_words);
+        }
+
+        words
+    }
+}
+
+/// Replaces `\n`, `\t` and `\\` with the characters they stand for
+fn interpret_escape_sequences(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('n') => result.push('\n'),
+            Some('t') => result.push('\t'),
+            Some('\\') => result.push('\\'),
+            Some(other) => {
+                result.push('\\');
+                result.push(other);
+            }
+            None => result.push('\\'),
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_support::{TestShell, lock_process};
+    use std::env;
+
+    #[test]
+    fn echo_joins_its_arguments() {
+        let mut shell = TestShell::new();
+
+        assert_eq!(shell.run_output("echo hello   world"), "hello world\n");
+    }
+
+    #[test]
+    fn echo_n_drops_the_newline() {
+        let mut shell = TestShell::new();
+
+        assert_eq!(shell.run_output("echo -n hello"), "hello");
+    }
+
+    #[test]
+    fn echo_e_interprets_escapes() {
+        let mut shell = TestShell::new();
+
+        assert_eq!(shell.mbash.handle_echo_command(&["-e", r"a\tb"]), 0);
+        assert_eq!(shell.take_output(), "a\tb\n");
+        shell.mbash.handle_echo_command(&[r"a\tb"]);
+        assert_eq!(shell.take_output(), "a\\tb\n");
+    }
+
+    #[test]
+    fn history_lists_entered_lines_in_order() {
+        let mut shell = TestShell::new();
+        for line in ["echo one", "echo two", "echo three"] {
+            shell.mbash.handle_input(line);
+        }
+        shell.take_output();
+
+        assert_eq!(shell.mbash.history, ["echo one", "echo two", "echo three"]);
+        assert_eq!(
+            shell.run_output("history"),
+            "    1  echo one\n    2  echo two\n    3  echo three\n"
+        );
+        assert_eq!(
+            shell.run_output("history 2"),
+            "    2  echo two\n    3  echo three\n"
+        );
+    }
+
+    #[test]
+    fn history_drops_the_oldest_entries_past_the_limit() {
+        let mut shell = TestShell::new();
+        shell.mbash.history_limit = 2;
+        for line in ["echo one", "echo two", "echo three"] {
+            shell.mbash.handle_input(line);
+        }
+
+        assert_eq!(shell.mbash.history, ["echo two", "echo three"]);
+    }
+
+    #[test]
+    fn bang_bang_reruns_the_previous_command() {
+        let mut shell = TestShell::new();
+        shell.mbash.handle_input("echo again");
+        shell.take_output();
+
+        shell.mbash.handle_input("!!");
+        assert_eq!(shell.take_output(), "echo again\nagain\n");
+        assert_eq!(shell.mbash.history, ["echo again", "echo again"]);
+    }
+
+    #[test]
+    fn bang_number_picks_an_entry() {
+        let mut shell = TestShell::new();
+        for line in ["echo one", "echo two", "echo three"] {
+            shell.mbash.handle_input(line);
+        }
+        shell.take_output();
+
+        shell.mbash.handle_input("!2");
+        assert_eq!(shell.take_output(), "echo two\ntwo\n");
+    }
+
+    #[test]
+    fn out_of_range_history_runs_nothing() {
+        let mut shell = TestShell::new();
+        shell.mbash.handle_input("echo one");
+        shell.take_output();
+
+        shell.mbash.handle_input("!5");
+        assert_eq!(shell.take_output(), "");
+        assert_eq!(shell.mbash.last_exit_code, 1);
+        assert!(shell.logged("'!5' is out of range"));
+    }
+
+    #[test]
+    fn aliases_are_defined_listed_and_expanded() {
+        let mut shell = TestShell::new();
+
+        assert_eq!(shell.run("alias greet=\"echo hello\" bye='echo bye'"), 0);
+        assert_eq!(
+            shell.run_output("alias"),
+            "alias bye='echo bye'\nalias greet='echo hello'\n"
+        );
+        assert_eq!(shell.run_output("greet world"), "hello world\n");
+    }
+
+    #[test]
+    fn self_referencing_aliases_expand_once() {
+        let mut shell = TestShell::new();
+        shell.run("alias echo='echo -n'");
+
+        assert_eq!(shell.run_output("echo looped"), "looped");
+    }
+
+    #[test]
+    fn aliases_with_operators_are_refused() {
+        let mut shell = TestShell::new();
+
+        for definition in ["count='ls | wc -l'", "both='cd a && ls'", "save='ls > out'"] {
+            assert_eq!(shell.run(&format!("alias {}", definition)), 1);
+        }
+        assert!(shell.logged("can't contain '|', '&', ';', '<' or '>' outside of quotes"));
+        assert!(shell.mbash.aliases.is_empty());
+
+        assert_eq!(shell.run(r#"alias bar='echo "a | b"'"#), 0);
+        assert_eq!(shell.run_output("bar"), "a | b\n");
+    }
+
+    #[test]
+    fn unalias_removes_an_alias() {
+        let mut shell = TestShell::new();
+        shell.run("alias greet='echo hello'");
+
+        assert_eq!(shell.run("unalias greet"), 0);
+        assert!(shell.mbash.aliases.is_empty());
+        assert_eq!(shell.run("unalias greet"), 1);
+        assert!(shell.logged("Alias 'greet' isn't defined."));
+    }
+
+    #[test]
+    fn help_lists_commands_with_descriptions() {
+        let mut shell = TestShell::new();
+
+        let output = shell.run_output("help");
+        let names: Vec<&str> = output
+            .lines()
+            .filter_map(|line| line.split_whitespace().next())
+            .collect();
+        for name in ["ls", "cd", "exit"] {
+            assert!(names.contains(&name), "{} is missing", name);
+        }
+        let mut sorted = names.clone();
+        sorted.sort();
+        assert_eq!(names, sorted);
+    }
+
+    #[test]
+    fn help_describes_a_single_command() {
+        let mut shell = TestShell::new();
+
+        assert!(
+            shell
+                .run_output("help cd")
+                .starts_with("usage: cd [directory|-]\n")
+        );
+        assert_eq!(shell.run("help nope"), 1);
+        assert!(shell.logged("'nope' isn't an mbash command."));
+    }
+
+    #[test]
+    fn clear_writes_the_clear_screen_sequence() {
+        let mut shell = TestShell::new();
+
+        assert_eq!(shell.run_output("clear"), "\x1b[2J\x1b[H");
+        assert_eq!(shell.mbash.last_exit_code, 0);
+    }
+
+    #[test]
+    fn source_keeps_aliases_in_the_session() {
+        let mut shell = TestShell::new();
+        shell.write("aliases.mbash", "# shortcuts\nalias greet='echo hello'\n");
+
+        assert_eq!(shell.run("source aliases.mbash"), 0);
+        assert_eq!(shell.run_output("greet"), "hello\n");
+    }
+
+    #[test]
+    fn dot_sources_and_keeps_the_directory() {
+        let _guard = lock_process();
+        let mut shell = TestShell::new();
+        shell.run("mkdir sub");
+        shell.write("enter.mbash", "cd sub\n");
+
+        assert_eq!(shell.run(". enter.mbash"), 0);
+        assert!(shell.mbash.current_path.ends_with("sub"));
+    }
+
+    #[test]
+    fn source_reports_missing_files() {
+        let mut shell = TestShell::new();
+
+        assert_eq!(shell.run("source missing.mbash"), 1);
+    }
+
+    #[test]
+    fn which_finds_programs_in_path() {
+        let _guard = lock_process();
+        let mut shell = TestShell::new();
+
+        let output = shell.run_output("which sh");
+        assert!(output.starts_with('/'));
+        assert!(output.ends_with("/sh\n"));
+    }
+
+    #[test]
+    fn which_reports_builtins_and_missing_names() {
+        let _guard = lock_process();
+        let mut shell = TestShell::new();
+
+        assert_eq!(shell.run_output("which ls"), "ls: mbash builtin\n");
+        assert_eq!(shell.run("which mbash-test-missing"), 1);
+        assert!(shell.logged("'mbash-test-missing' not found in PATH."));
+    }
+
+    #[test]
+    fn type_classifies_builtins_aliases_and_programs() {
+        let _guard = lock_process();
+        let mut shell = TestShell::new();
+        shell.run("alias ll='ls -l'");
+
+        assert_eq!(shell.run_output("type ls"), "ls is an mbash builtin\n");
+        assert_eq!(shell.run_output("type ll"), "ll is aliased to 'ls -l'\n");
+        assert!(shell.run_output("type sh").starts_with("sh is /"));
+    }
+
+    #[test]
+    fn type_reports_unknown_names() {
+        let _guard = lock_process();
+        let mut shell = TestShell::new();
+
+        assert_eq!(shell.run("type mbash-test-missing"), 1);
+        assert!(shell.logged("isn't an alias, a builtin or a command in PATH"));
+    }
+
+    #[test]
+    fn set_variables_expand_until_unset() {
+        let mut guard = lock_process();
+        guard.set_var("MBASH_TEST_GREETING", None);
+        let mut shell = TestShell::new();
+
+        assert_eq!(shell.run("set MBASH_TEST_GREETING=hello"), 0);
+        assert_eq!(shell.run_output("echo $MBASH_TEST_GREETING"), "hello\n");
+        assert!(env::var_os("MBASH_TEST_GREETING").is_none());
+        assert!(
+            shell
+                .run_output("set")
+                .lines()
+                .any(|line| line == "MBASH_TEST_GREETING=hello")
+        );
+
+        assert_eq!(shell.run("unset MBASH_TEST_GREETING"), 0);
+        assert_eq!(shell.run_output("echo [$MBASH_TEST_GREETING]"), "[]\n");
+    }
+
+    #[test]
+    fn set_lists_variables_sorted() {
+        let _guard = lock_process();
+        let mut shell = TestShell::new();
+
+        let output = shell.run_output("set");
+        let names: Vec<&str> = output
+            .lines()
+            .filter_map(|line| line.split_once('=').map(|(name, _)| name))
+            .collect();
+        let mut sorted = names.clone();
+        sorted.sort();
+        assert_eq!(names, sorted);
+    }
+
+    #[test]
+    fn set_and_unset_reject_bad_names() {
+        let _guard = lock_process();
+        let mut shell = TestShell::new();
+
+        assert_eq!(shell.run("set 1abc=x"), 1);
+        assert!(shell.logged("requires a variable assignment"));
+        assert_eq!(shell.run("unset 1abc"), 1);
+        assert!(shell.logged("'1abc' isn't a valid variable name."));
+    }
+
+    #[test]
+    fn only_exported_variables_reach_programs() {
+        let mut guard = lock_process();
+        guard.set_var("MBASH_TEST_SHARED", None);
+        let mut shell = TestShell::new();
+
+        shell.run("set MBASH_TEST_SHARED=local");
+        assert_eq!(
+            shell.run_output("sh -c 'echo [$MBASH_TEST_SHARED]'"),
+            "[]\n"
+        );
+
+        assert_eq!(shell.run("export MBASH_TEST_SHARED=exported"), 0);
+        assert_eq!(
+            shell.run_output("sh -c 'echo [$MBASH_TEST_SHARED]'"),
+            "[exported]\n"
+        );
+        assert_eq!(
+            shell.run_output("export"),
+            "export MBASH_TEST_SHARED=exported\n"
+        );
+    }
+
+    #[test]
+    fn unset_hides_inherited_variables_without_changing_the_environment() {
+        let mut guard = lock_process();
+        guard.set_var("MBASH_TEST_INHERITED", Some("parent"));
+        let mut shell = TestShell::new();
+
+        assert_eq!(shell.run_output("echo $MBASH_TEST_INHERITED"), "parent\n");
+        assert_eq!(shell.run("unset MBASH_TEST_INHERITED"), 0);
+        assert_eq!(shell.run_output("echo [$MBASH_TEST_INHERITED]"), "[]\n");
+        assert_eq!(
+            shell.run_output("sh -c 'echo [$MBASH_TEST_INHERITED]'"),
+            "[]\n"
+        );
+        assert_eq!(env::var("MBASH_TEST_INHERITED").unwrap(), "parent");
+    }
+
+    #[test]
+    fn time_reports_how_long_a_command_took() {
+        let mut shell = TestShell::new();
+
+        let output = shell.run_output("time echo hi");
+        let (echoed, timing) = output.split_once('\n').unwrap();
+        assert_eq!(echoed, "hi");
+        let elapsed = timing
+            .strip_prefix("real ")
+            .and_then(|timing| timing.trim_end().strip_suffix("ms"))
+            .unwrap();
+        assert!(elapsed.parse::<f64>().unwrap() >= 0.0);
+    }
+
+    #[test]
+    fn time_keeps_the_command_status() {
+        let mut shell = TestShell::new();
+
+        assert_eq!(shell.run("time cat missing.txt"), 1);
+        assert_eq!(shell.run("time"), 1);
+    }
+
+    /// Puts an executable `mbash-greet` script on a new directory prepended to `PATH`
+    #[cfg(unix)]
+    fn install_greet_plugin(
+        shell: &TestShell,
+        guard: &mut super::super::test_support::ProcessGuard,
+    ) {
+        use std::os::unix::fs::PermissionsExt;
+
+        shell.write(
+            "plugins/mbash-greet",
+            "#!/bin/sh\necho \"greet $* in $MBASH_CURRENT_DIR\"\n",
+        );
+        let plugin = shell.path("plugins/mbash-greet");
+        std::fs::set_permissions(&plugin, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let path = format!(
+            "{}:{}",
+            shell.path("plugins").display(),
+            std::env::var("PATH").unwrap_or_default()
+        );
+        guard.set_var("PATH", Some(&path));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn plugins_run_with_their_arguments_and_directory() {
+        let mut guard = lock_process();
+        let mut shell = TestShell::new();
+        install_greet_plugin(&shell, &mut guard);
+
+        assert_eq!(
+            shell.run_output("greet you all"),
+            format!("greet you all in {}\n", shell.mbash.current_path.display())
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn plugins_are_listed_in_help() {
+        let mut guard = lock_process();
+        let mut shell = TestShell::new();
+        install_greet_plugin(&shell, &mut guard);
+
+        assert!(shell.run_output("help").contains("greet"));
+        assert!(
+            shell
+                .run_output("help greet")
+                .starts_with("greet is a plugin run from ")
+        );
+        assert!(
+            shell
+                .run_output("type greet")
+                .starts_with("greet is an mbash plugin at ")
+        );
+    }
+
+    #[test]
+    fn version_prints_the_name_and_version() {
+        let mut shell = TestShell::new();
+
+        assert_eq!(
+            shell.run_output("version"),
+            format!("mbash {}\n", env!("CARGO_PKG_VERSION"))
+        );
+    }
+}
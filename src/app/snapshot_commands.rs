@@ -0,0 +1,496 @@
+use logger::{debug, error, info};
+use similar::TextDiff;
+use std::{
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use super::{Mbash, SNAPSHOTS_DIR_PATH};
+use crate::helper_functions;
+
+/// Name of the file inside each snapshot listing `<stored copy>\t<original path>` lines
+const MANIFEST_FILE_NAME: &str = "manifest";
+
+impl Mbash {
+    pub(super) fn handle_snapshot_command(&mut self, _args: &[&str]) -> i32 {
+        if self.tracking_files.is_empty() {
+            error!(
+                self.logger,
+                "No files are being tracked, nothing to snapshot."
+            );
+            return 1;
+        }
+
+        let snapshots_dir = self.snapshots_dir();
+        let (snapshot_id, snapshot_dir) = match create_snapshot_dir(&snapshots_dir) {
+            Ok(snapshot) => snapshot,
+            Err(e) => {
+                error!(
+                    self.logger,
+                    "Failed to create a snapshot directory in '{}': '{}'.",
+                    snapshots_dir.display(),
+                    e
+                );
+                return 1;
+            }
+        };
+
+        let mut status = 0;
+        let mut manifest = String::new();
+        for (index, tracked) in self.tracking_files.iter().enumerate() {
+            let original = Path::new(tracked);
+            let file_name = original
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let stored_name = format!("{}-{}", index, file_name);
+
+            match fs::copy(original, snapshot_dir.join(&stored_name)) {
+                Ok(_) => {
+                    debug!(self.logger, "Stored '{}' as '{}'.", tracked, stored_name);
+                    manifest.push_str(&format!("{}\t{}\n", stored_name, tracked));
+                }
+                Err(e) => {
+                    error!(self.logger, "Failed to snapshot '{}': '{}'.", tracked, e);
+                    status = 1;
+                }
+            }
+        }
+
+        if let Err(e) = fs::write(snapshot_dir.join(MANIFEST_FILE_NAME), manifest) {
+            error!(
+                self.logger,
+                "Failed to write the manifest of snapshot '{}': '{}'.", snapshot_id, e
+            );
+            return 1;
+        }
+
+        let _ = writeln!(self.output, "Created snapshot {}", snapshot_id);
+        status
+    }
+
+    pub(super) fn handle_restore_command(&mut self, args: &[&str]) -> i32 {
+        let snapshot_id = match args {
+            [] => return self.list_snapshots(),
+            [snapshot_id] => *snapshot_id,
+            _ => {
+                error!(
+                    self.logger,
+                    "'restore' command accepts a single snapshot [restore [snapshot]]."
+                );
+                return 1;
+            }
+        };
+
+        let snapshot_dir = self.snapshots_dir().join(snapshot_id);
+        if snapshot_id.contains(['/', '\\']) || !snapshot_dir.is_dir() {
+            error!(self.logger, "Snapshot '{}' doesn't exist.", snapshot_id);
+            return 1;
+        }
+
+        let Some(entries) = self.read_manifest(snapshot_id, &snapshot_dir) else {
+            return 1;
+        };
+
+        let mut status = 0;
+        for (stored, original) in entries {
+            if !original.exists() {
+                info!(
+                    self.logger,
+                    "Skipping '{}', it is no longer at its original location.",
+                    original.display()
+                );
+                continue;
+            }
+
+            if self.skip_for_dry_run(&format!("restore '{}'", original.display())) {
+                continue;
+            }
+
+            match fs::copy(&stored, &original) {
+                Ok(_) => debug!(self.logger, "Restored '{}'.", original.display()),
+                Err(e) => {
+                    error!(
+                        self.logger,
+                        "Failed to restore '{}': '{}'.",
+                        original.display(),
+                        e
+                    );
+                    status = 1;
+                }
+            }
+        }
+
+        if !self.dry_run {
+            let _ = writeln!(self.output, "Restored snapshot {}", snapshot_id);
+        }
+        status
+    }
+
+    pub(super) fn handle_diff_command(&mut self, args: &[&str]) -> i32 {
+        let explicit_paths = !args.is_empty();
+        let paths: Vec<String> = if explicit_paths {
+            args.iter()
+                .map(|arg| {
+                    let path = helper_functions::normalize_path(&self.resolve_path(arg));
+                    path.to_string_lossy().into_owned()
+                })
+                .collect()
+        } else {
+            self.tracking_files.clone()
+        };
+
+        if paths.is_empty() {
+            error!(self.logger, "No files are being tracked, nothing to diff.");
+            return 1;
+        }
+
+        if snapshot_ids(&self.snapshots_dir()).is_ok_and(|snapshot_ids| snapshot_ids.is_empty()) {
+            let _ = writeln!(self.output, "No snapshots have been taken.");
+            return 0;
+        }
+
+        let mut status = 0;
+        for path in paths {
+            let Some(snapshot_copy) = self.latest_snapshot_copy(&path) else {
+                if explicit_paths {
+                    let _ = writeln!(self.output, "No snapshot of '{}' exists.", path);
+                }
+                continue;
+            };
+
+            let (old_contents, new_contents) = match (
+                fs::read_to_string(&snapshot_copy),
+                fs::read_to_string(&path),
+            ) {
+                (Ok(old_contents), Ok(new_contents)) => (old_contents, new_contents),
+                (Err(e), _) | (_, Err(e)) => {
+                    error!(self.logger, "Failed to diff '{}': '{}'.", path, e);
+                    status = 1;
+                    continue;
+                }
+            };
+
+            if old_contents == new_contents {
+                if explicit_paths {
+                    let _ = writeln!(self.output, "'{}' is unchanged.", path);
+                }
+                continue;
+            }
+
+            let diff = TextDiff::from_lines(&old_contents, &new_contents);
+            let snapshot_header = snapshot_copy.display().to_string();
+            let _ = write!(
+                self.output,
+                "{}",
+                diff.unified_diff().header(&snapshot_header, &path)
+            );
+        }
+
+        status
+    }
+
+    /// Finds the copy of `original` in the most recent snapshot that contains it
+    fn latest_snapshot_copy(&self, original: &str) -> Option<PathBuf> {
+        let snapshots_dir = self.snapshots_dir();
+        let snapshot_ids = snapshot_ids(&snapshots_dir).ok()?;
+
+        snapshot_ids.iter().rev().find_map(|snapshot_id| {
+            let snapshot_dir = snapshots_dir.join(snapshot_id);
+            self.read_manifest(snapshot_id, &snapshot_dir)?
+                .into_iter()
+                .find(|(_, path)| path == Path::new(original))
+                .map(|(stored, _)| stored)
+        })
+    }
+
+    fn list_snapshots(&mut self) -> i32 {
+        let snapshots_dir = self.snapshots_dir();
+        let snapshot_ids = match snapshot_ids(&snapshots_dir) {
+            Ok(snapshot_ids) => snapshot_ids,
+            Err(e) => {
+                error!(
+                    self.logger,
+                    "Failed to read '{}': '{}'.",
+                    snapshots_dir.display(),
+                    e
+                );
+                return 1;
+            }
+        };
+
+        if snapshot_ids.is_empty() {
+            let _ = writeln!(self.output, "No snapshots have been taken.");
+            return 0;
+        }
+
+        for snapshot_id in snapshot_ids {
+            let _ = writeln!(self.output, "{}", snapshot_id);
+        }
+        0
+    }
+
+    fn snapshots_dir(&self) -> PathBuf {
+        self.workspace_file(Path::new(SNAPSHOTS_DIR_PATH))
+    }
+
+    /// Reads a snapshot manifest into `(stored copy, original path)` pairs.
+    /// Returns `None` if any line is malformed or refers to a missing copy, so nothing is partially restored.
+    fn read_manifest(
+        &self,
+        snapshot_id: &str,
+        snapshot_dir: &Path,
+    ) -> Option<Vec<(PathBuf, PathBuf)>> {
+        let manifest = match fs::read_to_string(snapshot_dir.join(MANIFEST_FILE_NAME)) {
+            Ok(manifest) => manifest,
+            Err(e) => {
+                error!(
+                    self.logger,
+                    "Failed to read the manifest of snapshot '{}': '{}'.", snapshot_id, e
+                );
+                return None;
+            }
+        };
+
+        let mut entries = Vec::new();
+        for (index, line) in manifest.lines().enumerate() {
+            let Some((stored_name, original)) = line.split_once('\t') else {
+                error!(
+                    self.logger,
+                    "Line {} of the manifest of snapshot '{}' is malformed.",
+                    index + 1,
+                    snapshot_id
+                );
+                return None;
+            };
+
+            let stored = snapshot_dir.join(stored_name);
+            if !stored.is_file() {
+                error!(
+                    self.logger,
+                    "Snapshot '{}' is missing its copy of '{}'.", snapshot_id, original
+                );
+                return None;
+            }
+
+            entries.push((stored, PathBuf::from(original)));
+        }
+
+        Some(entries)
+    }
+}
+
+/// Returns the ids of all snapshots, oldest first
+fn snapshot_ids(snapshots_dir: &Path) -> io::Result<Vec<String>> {
+    let entries = match fs::read_dir(snapshots_dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+
+    let mut snapshot_ids: Vec<String> = entries
+        .filter_map(Result::ok)
+        .filter(|entry| entry.path().is_dir())
+        .map(|entry| entry.file_name().to_string_lossy().into_owned())
+        .collect();
+    snapshot_ids.sort_by(|a, b| snapshot_order(a).cmp(&snapshot_order(b)));
+    Ok(snapshot_ids)
+}
+
+/// Orders snapshot ids by their timestamp, then by the counter that `create_snapshot_dir` adds
+/// for same-second snapshots, so `<timestamp>-10` comes after `<timestamp>-2`
+fn snapshot_order(snapshot_id: &str) -> (&str, u64) {
+    // A timestamp like `2025-12-14T09-30-00` holds four dashes, a counter adds a fifth
+    match snapshot_id.rsplit_once('-') {
+        Some((timestamp, counter)) if timestamp.matches('-').count() == 4 => {
+            (timestamp, counter.parse().unwrap_or(0))
+        }
+        _ => (snapshot_id, 0),
+    }
+}
+
+/// Creates `.msnapshots/<timestamp>`, adding a counter when several snapshots share a second.
+/// The timestamp uses `-` instead of `:` so the id is a valid file name everywhere.
+fn create_snapshot_dir(snapshots_dir: &Path) -> io::Result<(String, PathBuf)> {
+    fs::create_dir_all(snapshots_dir)?;
+
+    let timestamp = helper_functions::format_timestamp(SystemTime::now()).replace(':', "-");
+    let mut snapshot_id = timestamp.clone();
+    let mut counter = 1;
+    loop {
+        let snapshot_dir = snapshots_dir.join(&snapshot_id);
+        match fs::create_dir(&snapshot_dir) {
+            Ok(()) => return Ok((snapshot_id, snapshot_dir)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                snapshot_id = format!("{}-{}", timestamp, counter);
+                counter += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_support::TestShell;
+    use super::*;
+
+    /// A workspace tracking `notes.txt`
+    fn workspace() -> TestShell {
+        let mut shell = TestShell::new();
+        shell.write("notes.txt", "first\n");
+        assert_eq!(shell.run("init"), 0);
+        assert_eq!(shell.run("add notes.txt"), 0);
+        shell.take_output();
+        shell
+    }
+
+    fn snapshot(shell: &mut TestShell) -> String {
+        let output = shell.run_output("snapshot");
+        assert_eq!(shell.mbash.last_exit_code, 0);
+        output
+            .trim_end()
+            .strip_prefix("Created snapshot ")
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn snapshots_keep_the_contents_they_copied() {
+        let mut shell = workspace();
+        let snapshot_id = snapshot(&mut shell);
+        shell.write("notes.txt", "changed\n");
+
+        let snapshot_dir = shell.path(".msnapshots").join(&snapshot_id);
+        let entries = shell
+            .mbash
+            .read_manifest(&snapshot_id, &snapshot_dir)
+            .unwrap();
+        assert_eq!(entries.len(), 1);
+        let (stored, original) = &entries[0];
+        assert_eq!(*original, shell.path("notes.txt"));
+        assert_eq!(fs::read_to_string(stored).unwrap(), "first\n");
+    }
+
+    #[test]
+    fn snapshot_without_tracked_files_fails() {
+        let mut shell = TestShell::new();
+        shell.run("init");
+
+        assert_eq!(shell.run("snapshot"), 1);
+        assert!(shell.logged("nothing to snapshot"));
+    }
+
+    #[test]
+    fn snapshots_in_the_same_second_sort_by_counter() {
+        let shell = TestShell::new();
+        let snapshots_dir = shell.path(".msnapshots");
+        for snapshot_id in [
+            "2025-12-14T09-30-00-10",
+            "2025-12-14T09-30-00",
+            "2025-12-14T09-30-00-2",
+            "2025-12-14T09-29-59",
+        ] {
+            fs::create_dir_all(snapshots_dir.join(snapshot_id)).unwrap();
+        }
+
+        assert_eq!(
+            snapshot_ids(&snapshots_dir).unwrap(),
+            [
+                "2025-12-14T09-29-59",
+                "2025-12-14T09-30-00",
+                "2025-12-14T09-30-00-2",
+                "2025-12-14T09-30-00-10",
+            ]
+        );
+    }
+
+    #[test]
+    fn dry_run_restore_keeps_the_current_contents() {
+        let mut shell = workspace();
+        let snapshot_id = snapshot(&mut shell);
+        shell.write("notes.txt", "changed\n");
+        shell.run("set dryrun on");
+
+        let output = shell.run_output(&format!("restore {}", snapshot_id));
+        assert!(output.starts_with("dry run: would restore"));
+        assert_eq!(shell.read("notes.txt"), "changed\n");
+    }
+
+    #[test]
+    fn restore_brings_back_the_snapshot_contents() {
+        let mut shell = workspace();
+        let snapshot_id = snapshot(&mut shell);
+        shell.write("notes.txt", "changed\n");
+
+        assert_eq!(
+            shell.run_output(&format!("restore {}", snapshot_id)),
+            format!("Restored snapshot {}\n", snapshot_id)
+        );
+        assert_eq!(shell.read("notes.txt"), "first\n");
+    }
+
+    #[test]
+    fn restore_lists_snapshots_without_an_argument() {
+        let mut shell = workspace();
+        assert_eq!(
+            shell.run_output("restore"),
+            "No snapshots have been taken.\n"
+        );
+
+        let snapshot_id = snapshot(&mut shell);
+        assert_eq!(shell.run_output("restore"), format!("{}\n", snapshot_id));
+    }
+
+    #[test]
+    fn restore_skips_files_that_moved() {
+        let mut shell = workspace();
+        let snapshot_id = snapshot(&mut shell);
+        fs::rename(shell.path("notes.txt"), shell.path("moved.txt")).unwrap();
+
+        assert_eq!(shell.run(&format!("restore {}", snapshot_id)), 0);
+        assert!(!shell.path("notes.txt").exists());
+        assert!(shell.logged("it is no longer at its original location"));
+    }
+
+    #[test]
+    fn restore_rejects_unknown_snapshots() {
+        let mut shell = workspace();
+
+        assert_eq!(shell.run("restore nope"), 1);
+        assert_eq!(shell.run("restore ../notes.txt"), 1);
+        assert!(shell.logged("Snapshot 'nope' doesn't exist."));
+    }
+
+    #[test]
+    fn diff_shows_changed_lines() {
+        let mut shell = workspace();
+        snapshot(&mut shell);
+        shell.write("notes.txt", "second\n");
+
+        let output = shell.run_output("diff notes.txt");
+        let changes: Vec<&str> = output
+            .lines()
+            .filter(|line| line.starts_with(['-', '+']))
+            .filter(|line| !line.starts_with("---") && !line.starts_with("+++"))
+            .collect();
+        assert_eq!(changes, ["-first", "+second"]);
+        // Without arguments every changed tracked file is compared
+        assert_eq!(shell.run_output("diff"), output);
+    }
+
+    #[test]
+    fn diff_reports_unchanged_files_and_missing_snapshots() {
+        let mut shell = workspace();
+        assert_eq!(shell.run_output("diff"), "No snapshots have been taken.\n");
+
+        snapshot(&mut shell);
+        assert_eq!(shell.run_output("diff"), "");
+        assert_eq!(
+            shell.run_output("diff notes.txt"),
+            format!("'{}' is unchanged.\n", shell.path("notes.txt").display())
+        );
+    }
+}
@@ -0,0 +1,141 @@
+use logger::error;
+use std::{
+    fs::{self, Metadata, Permissions},
+    io::{self, Write},
+    time::SystemTime,
+};
+
+use super::Mbash;
+use crate::helper_functions;
+
+impl Mbash {
+    pub(super) fn handle_stat_command(&mut self, args: &[&str]) -> i32 {
+        if args.is_empty() {
+            error!(
+                self.logger,
+                "'stat' command requires at least one path [stat <path>...]."
+            );
+            return 1;
+        }
+
+        let mut status = 0;
+        for target in args {
+            let path = self.resolve_path(target);
+            let metadata = match fs::metadata(&path) {
+                Ok(metadata) => metadata,
+                Err(e) => {
+                    error!(self.logger, "Cannot stat '{}': '{}'.", path.display(), e);
+                    status = 1;
+                    continue;
+                }
+            };
+
+            let _ = writeln!(self.output, "    File: {}", target);
+            let _ = writeln!(self.output, "    Type: {}", file_type_name(&metadata));
+            let _ = writeln!(self.output, "    Size: {} bytes", metadata.len());
+            let _ = writeln!(
+                self.output,
+                "  Access: {}",
+                format_permissions(&metadata.permissions())
+            );
+            let _ = writeln!(
+                self.output,
+                "Modified: {}",
+                format_time(metadata.modified())
+            );
+            let _ = writeln!(
+                self.output,
+                "Accessed: {}",
+                format_time(metadata.accessed())
+            );
+            let _ = writeln!(self.output, " Created: {}", format_time(metadata.created()));
+        }
+
+        status
+    }
+}
+
+fn file_type_name(metadata: &Metadata) -> &'static str {
+    if metadata.is_dir() {
+        "directory"
+    } else if metadata.is_file() {
+        "regular file"
+    } else {
+        "other"
+    }
+}
+
+/// Formats permissions as octal plus an `rwx` string, e.g. `0644 (rw-r--r--)`
+#[cfg(unix)]
+fn format_permissions(permissions: &Permissions) -> String {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mode = permissions.mode() & 0o777;
+    let symbolic: String = (0..9)
+        .map(|bit| {
+            if mode & (0o400 >> bit) == 0 {
+                '-'
+            } else {
+                ['r', 'w', 'x'][bit % 3]
+            }
+        })
+        .collect();
+
+    format!("{:04o} ({})", mode, symbolic)
+}
+
+#[cfg(not(unix))]
+fn format_permissions(permissions: &Permissions) -> String {
+    if permissions.readonly() {
+        String::from("read-only")
+    } else {
+        String::from("read-write")
+    }
+}
+
+fn format_time(time: io::Result<SystemTime>) -> String {
+    match time {
+        Ok(time) => helper_functions::format_timestamp(time),
+        Err(_) => String::from("unavailable"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_support::TestShell;
+
+    #[test]
+    fn stat_reports_the_size_and_type() {
+        let mut shell = TestShell::new();
+        shell.write("known.txt", "hello");
+
+        let output = shell.run_output("stat known.txt");
+        assert!(output.contains("File: known.txt\n"));
+        assert!(output.contains("Type: regular file\n"));
+        assert!(output.contains("Size: 5 bytes\n"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn stat_shows_permissions_in_octal_and_rwx() {
+        use std::{fs, os::unix::fs::PermissionsExt};
+
+        let mut shell = TestShell::new();
+        shell.write("script.sh", "");
+        fs::set_permissions(shell.path("script.sh"), fs::Permissions::from_mode(0o754)).unwrap();
+
+        assert!(
+            shell
+                .run_output("stat script.sh")
+                .contains("Access: 0754 (rwxr-xr--)\n")
+        );
+    }
+
+    #[test]
+    fn stat_reports_missing_files() {
+        let mut shell = TestShell::new();
+
+        assert_eq!(shell.run("stat missing.txt"), 1);
+        assert!(shell.logged("Cannot stat"));
+    }
+}
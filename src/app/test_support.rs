@@ -0,0 +1,153 @@
+use logger::{LogLevel, Logger};
+use std::{
+    env,
+    ffi::OsString,
+    fs,
+    path::PathBuf,
+    sync::{Arc, Mutex, MutexGuard},
+};
+use tempfile::TempDir;
+
+use super::Mbash;
+use crate::output_sink::OutputSink;
+
+/// Serializes tests that change the process's directory or environment
+static PROCESS_LOCK: Mutex<()> = Mutex::new(());
+
+/// Keeps every log message so tests can check what a command reported
+#[derive(Clone, Default)]
+pub(super) struct CapturingLogger {
+    messages: Arc<Mutex<Vec<String>>>,
+}
+
+impl Logger for CapturingLogger {
+    fn log(&self, level: LogLevel, message: &str) {
+        let level = match level {
+            LogLevel::DEBUG => "DEBUG",
+            LogLevel::INFO => "INFO",
+            LogLevel::ERROR => "ERROR",
+        };
+        self.messages
+            .lock()
+            .unwrap()
+            .push(format!("[{}] {}", level, message));
+    }
+}
+
+/// Holds the process lock and puts the working directory and any variables it set back when
+/// dropped
+pub(super) struct ProcessGuard {
+    directory: PathBuf,
+    variables: Vec<(String, Option<OsString>)>,
+    _lock: MutexGuard<'static, ()>,
+}
+
+impl ProcessGuard {
+    /// Sets or, given `None`, removes an environment variable until the guard is dropped
+    pub(super) fn set_var(&mut self, name: &str, value: Option<&str>) {
+        if !self.variables.iter().any(|(saved, _)| saved == name) {
+            self.variables.push((name.to_string(), env::var_os(name)));
+        }
+        // SAFETY: the process lock keeps other tests from touching the environment meanwhile
+        unsafe {
+            match value {
+                Some(value) => env::set_var(name, value),
+                None => env::remove_var(name),
+            }
+        }
+    }
+}
+
+impl Drop for ProcessGuard {
+    fn drop(&mut self) {
+        let _ = env::set_current_dir(&self.directory);
+        for (name, value) in self.variables.drain(..).rev() {
+            // SAFETY: the process lock is still held
+            unsafe {
+                match value {
+                    Some(value) => env::set_var(name, value),
+                    None => env::remove_var(name),
+                }
+            }
+        }
+    }
+}
+
+/// Takes the process lock, for tests that `cd` or set environment variables
+pub(super) fn lock_process() -> ProcessGuard {
+    let lock = PROCESS_LOCK
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    ProcessGuard {
+        directory: env::current_dir().unwrap(),
+        variables: Vec::new(),
+        _lock: lock,
+    }
+}
+
+/// An `Mbash` working in its own temporary directory, with its output and logs captured
+pub(super) struct TestShell {
+    pub(super) mbash: Mbash,
+    logger: CapturingLogger,
+    _directory: TempDir,
+}
+
+impl TestShell {
+    pub(super) fn new() -> Self {
+        let directory = TempDir::new().unwrap();
+        let logger = CapturingLogger::default();
+        let mut mbash = Mbash::new(Box::new(logger.clone()));
+        mbash.current_path = directory.path().canonicalize().unwrap();
+        mbash.history_file = Some(mbash.current_path.join(".mhistory"));
+        mbash.set_output(OutputSink::Buffer(Vec::new()));
+        TestShell {
+            mbash,
+            logger,
+            _directory: directory,
+        }
+    }
+
+    /// Runs a command line and returns its exit status
+    pub(super) fn run(&mut self, command_line: &str) -> i32 {
+        self.mbash.execute_line(command_line);
+        self.mbash.last_exit_code
+    }
+
+    /// Runs a command line and returns what it printed
+    pub(super) fn run_output(&mut self, command_line: &str) -> String {
+        self.run(command_line);
+        self.take_output()
+    }
+
+    /// Returns the output printed since the last call
+    pub(super) fn take_output(&mut self) -> String {
+        let output = self.mbash.set_output(OutputSink::Buffer(Vec::new()));
+        String::from_utf8(output.into_buffer()).unwrap()
+    }
+
+    pub(super) fn logs(&self) -> Vec<String> {
+        self.logger.messages.lock().unwrap().clone()
+    }
+
+    /// Tells whether any log message contains `text`
+    pub(super) fn logged(&self, text: &str) -> bool {
+        self.logs().iter().any(|message| message.contains(text))
+    }
+
+    pub(super) fn path(&self, relative: &str) -> PathBuf {
+        self.mbash.current_path.join(relative)
+    }
+
+    /// Writes a file relative to the current path, creating its parent directories
+    pub(super) fn write(&self, relative: &str, contents: &str) {
+        let path = self.path(relative);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).unwrap();
+        }
+        fs::write(path, contents).unwrap();
+    }
+
+    pub(super) fn read(&self, relative: &str) -> String {
+        fs::read_to_string(self.path(relative)).unwrap()
+    }
+}
@@ -0,0 +1,449 @@
+use logger::{debug, error};
+use std::{
+    fs::{self, File},
+    io::{Read, Seek, SeekFrom, Write},
+    ops::Range,
+    path::Path,
+    sync::atomic::Ordering,
+    thread,
+    time::Duration,
+};
+
+use super::Mbash;
+
+const DEFAULT_LINE_COUNT: usize = 10;
+/// How often `tail -f` checks the followed file for new content
+const FOLLOW_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+struct LineOptions<'a> {
+    count: usize,
+    follow: bool,
+    file_names: Vec<&'a str>,
+}
+
+#[derive(Default)]
+struct Counts {
+    lines: usize,
+    words: usize,
+    bytes: usize,
+}
+
+impl Counts {
+    fn of(contents: &[u8]) -> Self {
+        Counts {
+            lines: contents.iter().filter(|byte| **byte == b'\n').count(),
+            words: String::from_utf8_lossy(contents).split_whitespace().count(),
+            bytes: contents.len(),
+        }
+    }
+}
+
+#[derive(Default)]
+struct CountSelection {
+    lines: bool,
+    words: bool,
+    bytes: bool,
+}
+
+impl CountSelection {
+    fn values(&self, counts: &Counts) -> Vec<usize> {
+        [
+            (self.lines, counts.lines),
+            (self.words, counts.words),
+            (self.bytes, counts.bytes),
+        ]
+        .into_iter()
+        .filter_map(|(selected, value)| selected.then_some(value))
+        .collect()
+    }
+}
+
+impl Mbash {
+    pub(super) fn handle_wc_command(&mut self, args: &[&str]) -> i32 {
+        let mut selection = CountSelection::default();
+        let mut file_names = Vec::new();
+
+        for arg in args {
+            match arg.strip_prefix('-') {
+                Some(flags) if !flags.is_empty() => {
+                    for flag in flags.chars() {
+                        match flag {
+                            'l' => selection.lines = true,
+                            'w' => selection.words = true,
+                            'c' => selection.bytes = true,
+                            _ => {
+                                error!(
+                                    self.logger,
+                                    "'wc' doesn't support '-{}' [wc [-l] [-w] [-c] [file...]].",
+                                    flag
+                                );
+                                return 1;
+                            }
+                        }
+                    }
+                }
+                _ => file_names.push(*arg),
+            }
+        }
+
+        if !selection.lines && !selection.words && !selection.bytes {
+            selection = CountSelection {
+                lines: true,
+                words: true,
+                bytes: true,
+            };
+        }
+
+        let mut status = 0;
+        let mut rows: Vec<(Counts, &str)> = Vec::new();
+        if file_names.is_empty() {
+            let Some(input) = self.input.take() else {
+                error!(
+                    self.logger,
+                    "'wc' command requires a file or piped input [wc [-l] [-w] [-c] [file...]]."
+                );
+                return 1;
+            };
+            rows.push((Counts::of(&input), ""));
+        }
+
+        for file_name in &file_names {
+            let path = self.resolve_path(file_name);
+            match fs::read(&path) {
+                Ok(contents) => rows.push((Counts::of(&contents), file_name)),
+                Err(e) => {
+                    error!(self.logger, "Failed to read '{}': '{}'.", path.display(), e);
+                    status = 1;
+                }
+            }
+        }
+
+        if rows.len() > 1 {
+            let total = rows
+                .iter()
+                .fold(Counts::default(), |total, (counts, _)| Counts {
+                    lines: total.lines + counts.lines,
+                    words: total.words + counts.words,
+                    bytes: total.bytes + counts.bytes,
+                });
+            rows.push((total, "total"));
+        }
+
+        // Columns are as wide as the largest count so that every row lines up
+        let width = rows
+            .iter()
+            .flat_map(|(counts, _)| selection.values(counts))
+            .max()
+            .map_or(1, |max| max.to_string().len());
+
+        for (counts, name) in rows {
+            let columns: Vec<String> = selection
+                .values(&counts)
+                .iter()
+                .map(|value| format!("{:>width$}", value, width = width))
+                .collect();
+            let _ = if name.is_empty() {
+                writeln!(self.output, "{}", columns.join(" "))
+            } else {
+                writeln!(self.output, "{} {}", columns.join(" "), name)
+            };
+        }
+
+        status
+    }
+
+    pub(super) fn handle_head_command(&mut self, args: &[&str]) -> i32 {
+        let Some(options) = self.parse_line_options("head", args) else {
+            return 1;
+        };
+
+        self.print_line_selection(&options, |length, count| 0..length.min(count))
+    }
+
+    pub(super) fn handle_tail_command(&mut self, args: &[&str]) -> i32 {
+        let Some(options) = self.parse_line_options("tail", args) else {
+            return 1;
+        };
+
+        if options.follow {
+            let [file_name] = options.file_names.as_slice() else {
+                error!(
+                    self.logger,
+                    "'tail -f' requires exactly one file [tail [-n count] [-f] <file>]."
+                );
+                return 1;
+            };
+            let path = self.resolve_path(file_name);
+            return self.follow_file(&path, options.count);
+        }
+
+        self.print_line_selection(&options, last_lines)
+    }
+
+    /// Parses `[-n count] [-f] [file...]`, shared by `head` and `tail`
+    fn parse_line_options<'a>(&self, command: &str, args: &[&'a str]) -> Option<LineOptions<'a>> {
+        let mut options = LineOptions {
+            count: DEFAULT_LINE_COUNT,
+            follow: false,
+            file_names: Vec::new(),
+        };
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match *arg {
+                "-n" => match args.next().and_then(|count| count.parse().ok()) {
+                    Some(count) => options.count = count,
+                    None => {
+                        error!(
+                            self.logger,
+                            "'{}' expects a number of lines after -n [{} [-n count] [file...]].",
+                            command,
+                            command
+                        );
+                        return None;
+                    }
+                },
+                "-f" if command == "tail" => options.follow = true,
+                _ => options.file_names.push(*arg),
+            }
+        }
+
+        Some(options)
+    }
+
+    /// Prints the range of lines `select` picks from each file, or from piped input when no file is given.
+    /// Several files are separated by `==> name <==` headers.
+    fn print_line_selection(
+        &mut self,
+        options: &LineOptions,
+        select: fn(usize, usize) -> Range<usize>,
+    ) -> i32 {
+        if options.file_names.is_empty() {
+            let Some(input) = self.input.take() else {
+                error!(self.logger, "A file or piped input is required.");
+                return 1;
+            };
+
+            let contents = String::from_utf8_lossy(&input).into_owned();
+            let lines: Vec<&str> = contents.lines().collect();
+            for line in &lines[select(lines.len(), options.count)] {
+                let _ = writeln!(self.output, "{}", line);
+            }
+            return 0;
+        }
+
+        let mut status = 0;
+        let show_headers = options.file_names.len() > 1;
+        for (index, file_name) in options.file_names.iter().enumerate() {
+            let path = self.resolve_path(file_name);
+            let contents = match fs::read(&path) {
+                Ok(contents) => String::from_utf8_lossy(&contents).into_owned(),
+                Err(e) => {
+                    error!(self.logger, "Failed to read '{}': '{}'.", path.display(), e);
+                    status = 1;
+                    continue;
+                }
+            };
+
+            if show_headers {
+                let separator = if index == 0 { "" } else { "\n" };
+                let _ = writeln!(self.output, "{}==> {} <==", separator, file_name);
+            }
+
+            let lines: Vec<&str> = contents.lines().collect();
+            for line in &lines[select(lines.len(), options.count)] {
+                let _ = writeln!(self.output, "{}", line);
+            }
+        }
+
+        status
+    }
+
+    /// Prints the last `count` lines of a file, then keeps printing whatever is appended
+    /// until Ctrl-C is pressed
+    fn follow_file(&mut self, path: &Path, count: usize) -> i32 {
+        let contents = match fs::read(path) {
+            Ok(contents) => contents,
+            Err(e) => {
+                error!(self.logger, "Failed to read '{}': '{}'.", path.display(), e);
+                return 1;
+            }
+        };
+
+        let text = String::from_utf8_lossy(&contents).into_owned();
+        let lines: Vec<&str> = text.lines().collect();
+        for line in &lines[last_lines(lines.len(), count)] {
+            let _ = writeln!(self.output, "{}", line);
+        }
+        let _ = self.output.flush();
+
+        let mut offset = contents.len() as u64;
+        self.interrupted.store(false, Ordering::Relaxed);
+        while !self.interrupted.load(Ordering::Relaxed) && !self.exiting.load(Ordering::Relaxed) {
+            thread::sleep(FOLLOW_POLL_INTERVAL);
+
+            let mut file = match File::open(path) {
+                Ok(file) => file,
+                Err(e) => {
+                    debug!(
+                        self.logger,
+                        "Couldn't reopen '{}': '{}'.",
+                        path.display(),
+                        e
+                    );
+                    continue;
+                }
+            };
+
+            let length = file.metadata().map_or(offset, |metadata| metadata.len());
+            if length < offset {
+                debug!(
+                    self.logger,
+                    "'{}' was truncated, following from the start.",
+                    path.display()
+                );
+                offset = 0;
+            }
+
+            let mut appended = Vec::new();
+            let read_result = file
+                .seek(SeekFrom::Start(offset))
+                .and_then(|_| file.read_to_end(&mut appended));
+            if let Ok(read) = read_result {
+                offset += read as u64;
+                let _ = self.output.write_all(&appended);
+                let _ = self.output.flush();
+            }
+        }
+
+        self.interrupted.store(false, Ordering::Relaxed);
+        0
+    }
+}
+
+/// Returns the range covering the last `count` of `length` lines
+fn last_lines(length: usize, count: usize) -> Range<usize> {
+    length.saturating_sub(count)..length
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs::OpenOptions,
+        io::Write,
+        sync::{
+            Arc,
+            atomic::{AtomicBool, Ordering},
+        },
+        thread,
+        time::Duration,
+    };
+
+    use super::super::test_support::TestShell;
+
+    fn numbered_lines(count: usize) -> String {
+        (1..=count).map(|line| format!("{}\n", line)).collect()
+    }
+
+    #[test]
+    fn wc_counts_lines_words_and_bytes() {
+        let mut shell = TestShell::new();
+        shell.write("notes.txt", "one two\nthree\n");
+
+        assert_eq!(shell.run_output("wc notes.txt"), " 2  3 14 notes.txt\n");
+        assert_eq!(shell.run_output("wc -l notes.txt"), "2 notes.txt\n");
+        assert_eq!(shell.run_output("wc -lw notes.txt"), "2 3 notes.txt\n");
+    }
+
+    #[test]
+    fn wc_totals_several_files() {
+        let mut shell = TestShell::new();
+        shell.write("a.txt", "a\n");
+        shell.write("b.txt", "b c\nd\n");
+
+        assert_eq!(
+            shell.run_output("wc -l a.txt b.txt"),
+            "1 a.txt\n2 b.txt\n3 total\n"
+        );
+    }
+
+    #[test]
+    fn wc_rejects_unknown_flags() {
+        let mut shell = TestShell::new();
+        shell.write("notes.txt", "");
+
+        assert_eq!(shell.run("wc -x notes.txt"), 1);
+        assert!(shell.logged("'wc' doesn't support '-x'"));
+    }
+
+    #[test]
+    fn head_and_tail_default_to_ten_lines() {
+        let mut shell = TestShell::new();
+        shell.write("numbers.txt", &numbered_lines(15));
+
+        assert_eq!(shell.run_output("head numbers.txt"), numbered_lines(10));
+        let tail: String = (6..=15).map(|line| format!("{}\n", line)).collect();
+        assert_eq!(shell.run_output("tail numbers.txt"), tail);
+    }
+
+    #[test]
+    fn head_and_tail_take_a_line_count() {
+        let mut shell = TestShell::new();
+        shell.write("numbers.txt", &numbered_lines(15));
+
+        assert_eq!(shell.run_output("head -n 2 numbers.txt"), "1\n2\n");
+        assert_eq!(shell.run_output("tail -n 2 numbers.txt"), "14\n15\n");
+        assert_eq!(shell.run("head -n many numbers.txt"), 1);
+        assert!(shell.logged("'head' expects a number of lines after -n"));
+    }
+
+    #[test]
+    fn head_and_tail_print_short_files_whole() {
+        let mut shell = TestShell::new();
+        shell.write("short.txt", "only\ntwo\n");
+
+        assert_eq!(shell.run_output("head -n 5 short.txt"), "only\ntwo\n");
+        assert_eq!(shell.run_output("tail -n 5 short.txt"), "only\ntwo\n");
+    }
+
+    #[test]
+    fn head_separates_several_files_with_headers() {
+        let mut shell = TestShell::new();
+        shell.write("a.txt", "a\n");
+        shell.write("b.txt", "b\n");
+
+        assert_eq!(
+            shell.run_output("head a.txt b.txt"),
+            "==> a.txt <==\na\n\n==> b.txt <==\nb\n"
+        );
+    }
+
+    #[test]
+    fn tail_follow_prints_appended_lines_until_interrupted() {
+        let mut shell = TestShell::new();
+        shell.write("log.txt", "old\n");
+
+        let path = shell.path("log.txt");
+        let interrupted = Arc::clone(&shell.mbash.interrupted);
+        let finished = Arc::new(AtomicBool::new(false));
+        let writer = {
+            let finished = Arc::clone(&finished);
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(300));
+                let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+                file.write_all(b"new\n").unwrap();
+                thread::sleep(Duration::from_millis(500));
+                // Stands in for Ctrl-C, repeated in case following only just started
+                while !finished.load(Ordering::Relaxed) {
+                    interrupted.store(true, Ordering::Relaxed);
+                    thread::sleep(Duration::from_millis(50));
+                }
+            })
+        };
+
+        assert_eq!(shell.mbash.handle_tail_command(&["-f", "log.txt"]), 0);
+        finished.store(true, Ordering::Relaxed);
+        writer.join().unwrap();
+        assert_eq!(shell.take_output(), "old\nnew\n");
+    }
+}
@@ -0,0 +1,756 @@
+use logger::{debug, error, info};
+use serde_json::{Value, json};
+use std::{
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use super::{HASHES_FILE_PATH, Mbash, SNAPSHOTS_DIR_PATH};
+use crate::{file_system, helper_functions};
+
+impl Mbash {
+    pub(super) fn handle_init_command(&mut self, args: &[&str]) -> i32 {
+        let force = match args {
+            [] => false,
+            ["--force"] => true,
+            _ => {
+                error!(
+                    self.logger,
+                    "'init' command only accepts --force [init [--force]]."
+                );
+                return 1;
+            }
+        };
+
+        let tracking_file = self.current_path.join(&self.tracking_file);
+        if self.file_system.metadata(&tracking_file).is_ok() && !force {
+            error!(
+                self.logger,
+                "'{}' already exists, use --force to re-initialize.",
+                tracking_file.display()
+            );
+            return 1;
+        }
+
+        self.save_tracking_changes();
+        let default_ignores = format!("{}/\ntarget/\n", SNAPSHOTS_DIR_PATH);
+        let initialization_result = self
+            .write_file(&tracking_file, "")
+            .and_then(|_| self.write_file(&self.current_path.join(HASHES_FILE_PATH), ""))
+            .and_then(|_| {
+                self.write_file(&self.current_path.join(&self.ignore_file), &default_ignores)
+            })
+            .and_then(|_| {
+                self.file_system
+                    .create_dir_all(&self.current_path.join(SNAPSHOTS_DIR_PATH))
+            });
+
+        if let Err(e) = initialization_result {
+            error!(self.logger, "Failed to initialize the workspace: '{}'.", e);
+            return 1;
+        }
+
+        // A workspace nested in another one takes over from it
+        self.root_path = Some(self.current_path.clone());
+        self.load_hashes_file();
+        self.load_ignore_file();
+        self.tracking_changed = false;
+        if let Err(e) = self.load_tracking_file() {
+            error!(self.logger, "{}", e);
+            return 1;
+        }
+
+        let _ = writeln!(
+            self.output,
+            "Initialized mbash workspace in {}",
+            self.current_path.display()
+        );
+        0
+    }
+
+    pub(super) fn handle_root_command(&mut self, _args: &[&str]) -> i32 {
+        match &self.root_path {
+            Some(root_path) => {
+                let _ = writeln!(self.output, "{}", root_path.display());
+                0
+            }
+            None => {
+                error!(
+                    self.logger,
+                    "Not inside an mbash workspace, run 'init' to create one."
+                );
+                1
+            }
+        }
+    }
+
+    pub(super) fn handle_add_command(&mut self, args: &[&str]) -> i32 {
+        if args.is_empty() {
+            error!(
+                self.logger,
+                "'add' command requires at least one path [add <path>...]."
+            );
+            return 1;
+        }
+
+        let mut status = 0;
+        let mut paths = Vec::new();
+        for arg in args {
+            match self.expand_add_argument(arg) {
+                Some(matches) => paths.extend(matches),
+                None => status = 1,
+            }
+        }
+
+        let mut newly_tracked = 0;
+        for path in paths {
+            self.record_hash(&path);
+            self.tracking_changed = true;
+            if self.tracking_files.contains(&path) {
+                debug!(
+                    self.logger,
+                    "'{}' is already tracked, updated its hash.", path
+                );
+                continue;
+            }
+
+            debug!(self.logger, "Started tracking '{}'.", path);
+            self.tracking_files.push(path);
+            newly_tracked += 1;
+        }
+
+        if args.contains(&".") {
+            let noun = if newly_tracked == 1 { "file" } else { "files" };
+            let _ = writeln!(self.output, "Tracked {} new {}", newly_tracked, noun);
+        }
+
+        status
+    }
+
+    pub(super) fn handle_save_command(&mut self, _args: &[&str]) -> i32 {
+        if self.save_tracking_changes() { 0 } else { 1 }
+    }
+
+    /// Resolves an `add` argument to normalized absolute paths. Glob patterns expand to the
+    /// files they match, while plain paths are taken as they are and must exist. Ignored files
+    /// are left out either way, which also covers patterns the shell already expanded.
+    /// `.` stands for every untracked file under the current path that isn't ignored. Unlike
+    /// `untracked -r` it includes hidden files, leaving out only mbash's own workspace files.
+    fn expand_add_argument(&self, arg: &str) -> Option<Vec<String>> {
+        if arg == "." {
+            let mut untracked = Vec::new();
+            let complete = self.collect_untracked(
+                &self.current_path,
+                Path::new(""),
+                true,
+                true,
+                &mut untracked,
+            );
+            let paths = untracked.into_iter().map(|(_, path)| path).collect();
+            return complete.then_some(paths);
+        }
+
+        let path = self.resolve_path(arg);
+        if !arg.contains(['*', '?', '[']) {
+            if self.file_system.metadata(&path).is_err() {
+                error!(self.logger, "'{}' does not exist.", path.display());
+                return None;
+            }
+            if self.is_ignored(arg) {
+                info!(self.logger, "'{}' is ignored, not tracking it.", arg);
+                return Some(Vec::new());
+            }
+            let path = helper_functions::normalize_path(&path);
+            return Some(vec![path.to_string_lossy().into_owned()]);
+        }
+
+        let matches = match file_system::glob(self.file_system.as_ref(), &path) {
+            Ok(matches) => matches,
+            Err(e) => {
+                error!(self.logger, "'{}' isn't a valid pattern: '{}'.", arg, e);
+                return None;
+            }
+        };
+
+        let paths: Vec<String> = matches
+            .into_iter()
+            .filter(|path| {
+                self.file_system
+                    .metadata(path)
+                    .is_ok_and(|metadata| metadata.is_file())
+            })
+            .map(|path| helper_functions::normalize_path(&path))
+            .filter(|path| {
+                let relative_path = path.strip_prefix(&self.current_path).unwrap_or(path);
+                !self.is_ignored(&relative_path.to_string_lossy())
+            })
+            .map(|path| path.to_string_lossy().into_owned())
+            .collect();
+
+        if paths.is_empty() {
+            info!(self.logger, "'{}' didn't match any files.", arg);
+        }
+        Some(paths)
+    }
+
+    /// Stores the current content hash of `path` so `status` can tell when it changes
+    fn record_hash(&mut self, path: &str) {
+        match self.hash_file(Path::new(path)) {
+            Ok(hash) => {
+                self.tracking_hashes.insert(path.to_string(), hash);
+            }
+            Err(e) => {
+                debug!(self.logger, "Couldn't hash '{}': '{}'.", path, e);
+                self.tracking_hashes.remove(path);
+            }
+        }
+    }
+
+    pub(super) fn handle_untrack_command(&mut self, args: &[&str]) -> i32 {
+        if args.is_empty() {
+            error!(
+                self.logger,
+                "'untrack' command requires at least one path [untrack <path>...]."
+            );
+            return 1;
+        }
+
+        let mut removed_any = false;
+        for arg in args {
+            let path = helper_functions::normalize_path(&self.resolve_path(arg));
+            let path = path.to_string_lossy();
+
+            match self
+                .tracking_files
+                .iter()
+                .position(|tracked| *tracked == path)
+            {
+                Some(index) => {
+                    self.tracking_files.remove(index);
+                    self.tracking_hashes.remove(path.as_ref());
+                    removed_any = true;
+                    debug!(self.logger, "Stopped tracking '{}'.", path);
+                }
+                None => debug!(self.logger, "'{}' isn't tracked, nothing to do.", path),
+            }
+        }
+
+        if removed_any {
+            self.tracking_changed = true;
+        }
+
+        0
+    }
+
+    pub(super) fn handle_untracked_command(&mut self, args: &[&str]) -> i32 {
+        let recursive = match args {
+            [] => false,
+            ["-r"] => true,
+            _ => {
+                error!(
+                    self.logger,
+                    "'untracked' command only accepts -r [untracked [-r]]."
+                );
+                return 1;
+            }
+        };
+
+        let mut untracked = Vec::new();
+        let complete = self.collect_untracked(
+            &self.current_path,
+            Path::new(""),
+            recursive,
+            false,
+            &mut untracked,
+        );
+        for (relative_path, _) in untracked {
+            let _ = writeln!(self.output, "{}", relative_path.display());
+        }
+
+        if complete { 0 } else { 1 }
+    }
+
+    /// Collects the files under `directory` that aren't tracked, skipping ignored entries and,
+    /// unless `include_hidden` is set, hidden ones. Files are collected as their path relative
+    /// to where the search started and their normalized absolute path.
+    /// Returns whether every directory could be read.
+    fn collect_untracked(
+        &self,
+        directory: &Path,
+        relative_directory: &Path,
+        recursive: bool,
+        include_hidden: bool,
+        untracked: &mut Vec<(PathBuf, String)>,
+    ) -> bool {
+        let mut entries: Vec<PathBuf> = match self.file_system.read_dir(directory) {
+            Ok(entries) => entries.into_iter().filter_map(Result::ok).collect(),
+            Err(e) => {
+                error!(
+                    self.logger,
+                    "Failed to read directory '{}': '{}'.",
+                    directory.display(),
+                    e
+                );
+                return false;
+            }
+        };
+        entries.sort();
+
+        let mut complete = true;
+        for entry in entries {
+            let Some(name) = entry.file_name() else {
+                continue;
+            };
+            let name = name.to_string_lossy().into_owned();
+            let relative_path = relative_directory.join(&name);
+            let hidden = name.starts_with('.') && !include_hidden;
+            if hidden
+                || self.is_workspace_metadata(&entry)
+                || self.is_ignored(&relative_path.to_string_lossy())
+            {
+                continue;
+            }
+
+            let is_directory = self
+                .file_system
+                .symlink_metadata(&entry)
+                .is_ok_and(|metadata| metadata.is_dir());
+            if is_directory {
+                if recursive
+                    && !self.collect_untracked(
+                        &entry,
+                        &relative_path,
+                        recursive,
+                        include_hidden,
+                        untracked,
+                    )
+                {
+                    complete = false;
+                }
+                continue;
+            }
+
+            let path = helper_functions::normalize_path(&entry);
+            let path = path.to_string_lossy().into_owned();
+            if !self.tracking_files.contains(&path) {
+                untracked.push((relative_path, path));
+            }
+        }
+
+        complete
+    }
+
+    /// Hashes a file's contents the way `.mhashes` stores them
+    fn hash_file(&self, path: &Path) -> io::Result<String> {
+        let contents = self.file_system.read(path)?;
+        Ok(helper_functions::hash_contents(&contents))
+    }
+
+    /// Tells whether `path` is one of the files mbash keeps in the workspace, like `.mtracking`,
+    /// or the history file
+    fn is_workspace_metadata(&self, path: &Path) -> bool {
+        [
+            self.tracking_file.as_path(),
+            Path::new(HASHES_FILE_PATH),
+            self.ignore_file.as_path(),
+            Path::new(SNAPSHOTS_DIR_PATH),
+        ]
+        .iter()
+        .any(|file_name| path == self.workspace_file(file_name))
+            || self.history_file.as_deref() == Some(path)
+    }
+
+    pub(super) fn handle_status_command(&mut self, args: &[&str]) -> i32 {
+        let json = match args {
+            [] => false,
+            ["--json"] => true,
+            _ => {
+                error!(
+                    self.logger,
+                    "'status' command only accepts --json [status [--json]]."
+                );
+                return 1;
+            }
+        };
+
+        let states: Vec<(&str, &String)> = self
+            .tracking_files
+            .iter()
+            .map(|path| (self.tracked_file_state(path), path))
+            .collect();
+
+        if json {
+            let entries: Vec<Value> = states
+                .iter()
+                .map(|(state, path)| json!({ "path": path, "state": state }))
+                .collect();
+            let _ = writeln!(self.output, "{}", Value::Array(entries));
+            return 0;
+        }
+
+        if states.is_empty() {
+            let _ = writeln!(self.output, "No files are being tracked.");
+            return 0;
+        }
+
+        for (state, path) in states {
+            let _ = writeln!(self.output, "{}: {}", state, path);
+        }
+
+        0
+    }
+
+    /// Compares a tracked file against its recorded hash
+    fn tracked_file_state(&self, path: &str) -> &'static str {
+        match self.hash_file(Path::new(path)) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => "missing",
+            Err(_) => "unreadable",
+            Ok(hash) => match self.tracking_hashes.get(path) {
+                Some(stored_hash) if *stored_hash == hash => "unchanged",
+                Some(_) => "modified",
+                None => "unknown",
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use serde_json::{Value, json};
+
+    use super::super::test_support::{TestShell, lock_process};
+    use crate::helper_functions;
+
+    /// A shell inside a freshly initialized workspace
+    fn workspace() -> TestShell {
+        let mut shell = TestShell::new();
+        assert_eq!(shell.run("init"), 0);
+        shell.take_output();
+        shell
+    }
+
+    fn tracked(shell: &TestShell, relative: &str) -> String {
+        shell.path(relative).display().to_string()
+    }
+
+    #[test]
+    fn add_tracks_a_file_once() {
+        let mut shell = workspace();
+        shell.write("notes.txt", "");
+
+        assert_eq!(shell.run("add notes.txt"), 0);
+        assert_eq!(shell.run("add notes.txt"), 0);
+        assert_eq!(shell.mbash.tracking_files, [tracked(&shell, "notes.txt")]);
+        assert!(shell.logged("is already tracked"));
+    }
+
+    #[test]
+    fn saved_tracking_file_matches_tracked_files() {
+        let mut shell = workspace();
+        shell.write("a.txt", "");
+        shell.write("b.txt", "");
+
+        assert_eq!(shell.run("add a.txt b.txt a.txt"), 0);
+        assert_eq!(shell.run("save"), 0);
+        assert_eq!(shell.read(".mtracking"), "a.txt\nb.txt\n");
+
+        shell.mbash.tracking_files.clear();
+        shell.mbash.load_tracking_file().unwrap();
+        assert_eq!(
+            shell.mbash.tracking_files,
+            [tracked(&shell, "a.txt"), tracked(&shell, "b.txt")]
+        );
+    }
+
+    #[test]
+    fn add_refuses_missing_paths() {
+        let mut shell = workspace();
+
+        assert_eq!(shell.run("add nothere"), 1);
+        assert!(shell.mbash.tracking_files.is_empty());
+        assert!(shell.logged("does not exist"));
+    }
+
+    #[test]
+    fn untrack_drops_the_entry_from_disk() {
+        let mut shell = workspace();
+        shell.write("a.txt", "");
+        shell.write("b.txt", "");
+        shell.run("add a.txt b.txt");
+
+        assert_eq!(shell.run("untrack a.txt"), 0);
+        assert_eq!(shell.mbash.tracking_files, [tracked(&shell, "b.txt")]);
+        assert_eq!(shell.run("save"), 0);
+        assert_eq!(shell.read(".mtracking"), "b.txt\n");
+    }
+
+    #[test]
+    fn untrack_ignores_untracked_paths() {
+        let mut shell = workspace();
+        shell.write("a.txt", "");
+        shell.run("add a.txt");
+
+        assert_eq!(shell.run("untrack other.txt"), 0);
+        assert_eq!(shell.mbash.tracking_files, [tracked(&shell, "a.txt")]);
+        assert!(shell.logged("isn't tracked, nothing to do"));
+    }
+
+    #[test]
+    fn status_marks_deleted_files_as_missing() {
+        let mut shell = workspace();
+        shell.write("kept.txt", "");
+        shell.write("gone.txt", "");
+        shell.run("add kept.txt gone.txt");
+        fs::remove_file(shell.path("gone.txt")).unwrap();
+
+        let output = shell.run_output("status");
+        let missing: Vec<&str> = output
+            .lines()
+            .filter(|line| line.starts_with("missing: "))
+            .collect();
+        assert_eq!(
+            missing,
+            [format!("missing: {}", tracked(&shell, "gone.txt"))]
+        );
+        assert!(output.contains(&format!("unchanged: {}", tracked(&shell, "kept.txt"))));
+    }
+
+    #[test]
+    fn status_reports_modified_files() {
+        let mut shell = workspace();
+        shell.write("notes.txt", "first");
+        shell.run("add notes.txt");
+        assert_eq!(shell.run("save"), 0);
+
+        let hash = helper_functions::hash_contents(b"first");
+        assert_eq!(shell.read(".mhashes"), format!("{}  notes.txt\n", hash));
+        let path = tracked(&shell, "notes.txt");
+        assert_eq!(shell.run_output("status"), format!("unchanged: {}\n", path));
+
+        shell.write("notes.txt", "second");
+        assert_eq!(shell.run_output("status"), format!("modified: {}\n", path));
+    }
+
+    #[test]
+    fn status_json_lists_paths_and_states() {
+        let mut shell = workspace();
+        shell.write("notes.txt", "");
+        shell.run("add notes.txt");
+
+        let output = shell.run_output("status --json");
+        let entries: Value = serde_json::from_str(&output).unwrap();
+        assert_eq!(
+            entries,
+            json!([{ "path": tracked(&shell, "notes.txt"), "state": "unchanged" }])
+        );
+    }
+
+    #[test]
+    fn init_reports_files_it_cannot_create() {
+        let mut shell = TestShell::new();
+        shell.write(".msnapshots", "in the way");
+
+        assert_eq!(shell.run("init"), 1);
+        assert!(shell.logged("Failed to initialize the workspace"));
+        assert!(shell.mbash.root_path.is_none());
+    }
+
+    #[test]
+    fn init_creates_the_workspace_files() {
+        let mut shell = TestShell::new();
+
+        assert_eq!(
+            shell.run_output("init"),
+            format!(
+                "Initialized mbash workspace in {}\n",
+                shell.mbash.current_path.display()
+            )
+        );
+        assert_eq!(shell.read(".mtracking"), "");
+        assert_eq!(shell.read(".mhashes"), "");
+        assert_eq!(shell.read(".mignoring"), ".msnapshots/\ntarget/\n");
+        assert!(shell.path(".msnapshots").is_dir());
+        assert_eq!(shell.mbash.ignore_patterns, [".msnapshots/", "target/"]);
+        assert_eq!(
+            shell.mbash.root_path.as_ref(),
+            Some(&shell.mbash.current_path)
+        );
+    }
+
+    #[test]
+    fn init_needs_force_to_reinitialize() {
+        let mut shell = workspace();
+        shell.write("notes.txt", "");
+        shell.run("add notes.txt");
+        shell.run("save");
+
+        assert_eq!(shell.run("init"), 1);
+        assert!(shell.logged("already exists, use --force to re-initialize"));
+        assert_eq!(shell.read(".mtracking"), "notes.txt\n");
+
+        assert_eq!(shell.run("init --force"), 0);
+        assert_eq!(shell.read(".mtracking"), "");
+        assert!(shell.mbash.tracking_files.is_empty());
+    }
+
+    #[test]
+    fn untracked_lists_files_that_are_not_tracked() {
+        let mut shell = workspace();
+        for file in [
+            "tracked.txt",
+            "new.txt",
+            ".hidden",
+            "target/build.o",
+            "src/lib.rs",
+        ] {
+            shell.write(file, "");
+        }
+        shell.run("add tracked.txt");
+
+        assert_eq!(shell.run_output("untracked"), "new.txt\n");
+        assert_eq!(shell.run_output("untracked -r"), "new.txt\nsrc/lib.rs\n");
+    }
+
+    #[test]
+    fn add_expands_glob_patterns() {
+        let mut shell = workspace();
+        shell.write(".mignoring", "secret.txt\n");
+        shell.mbash.load_ignore_file();
+        for file in ["a.txt", "b.txt", "secret.txt", "c.md"] {
+            shell.write(file, "");
+        }
+
+        assert_eq!(shell.run("add '*.txt'"), 0);
+        assert_eq!(
+            shell.mbash.tracking_files,
+            [tracked(&shell, "a.txt"), tracked(&shell, "b.txt")]
+        );
+    }
+
+    #[test]
+    fn add_leaves_out_ignored_files_the_shell_expanded() {
+        let mut shell = workspace();
+        shell.write(".mignoring", "secret.txt\n");
+        shell.mbash.load_ignore_file();
+        for file in ["a.txt", "secret.txt"] {
+            shell.write(file, "");
+        }
+
+        assert_eq!(shell.run("add *.txt"), 0);
+        assert_eq!(shell.mbash.tracking_files, [tracked(&shell, "a.txt")]);
+        assert!(shell.logged("'secret.txt' is ignored, not tracking it."));
+    }
+
+    #[test]
+    fn add_logs_patterns_without_matches() {
+        let mut shell = workspace();
+
+        assert_eq!(shell.run("add '*.rs'"), 0);
+        assert!(shell.mbash.tracking_files.is_empty());
+        assert!(shell.logged("didn't match any files"));
+    }
+
+    #[test]
+    fn tracking_resolves_against_the_root_from_a_subdirectory() {
+        let _guard = lock_process();
+        let mut shell = workspace();
+        let root = shell.mbash.current_path.clone();
+        shell.write("sub/inner.txt", "");
+
+        assert_eq!(shell.run("cd sub"), 0);
+        assert_eq!(shell.mbash.root_path.as_ref(), Some(&root));
+        assert_eq!(shell.run("add inner.txt"), 0);
+        assert_eq!(
+            shell.mbash.tracking_files,
+            [root.join("sub/inner.txt").display().to_string()]
+        );
+
+        shell.mbash.save_tracking_changes();
+        assert_eq!(
+            fs::read_to_string(root.join(".mtracking")).unwrap(),
+            "sub/inner.txt\n"
+        );
+        assert!(!shell.path(".mtracking").exists());
+    }
+
+    #[test]
+    fn tracked_paths_outside_the_root_stay_absolute() {
+        let outside = tempfile::TempDir::new().unwrap();
+        let outside_file = outside.path().canonicalize().unwrap().join("far.txt");
+        fs::write(&outside_file, "").unwrap();
+        let mut shell = workspace();
+
+        assert_eq!(shell.run(&format!("add {}", outside_file.display())), 0);
+        shell.mbash.save_tracking_changes();
+        assert_eq!(
+            shell.read(".mtracking"),
+            format!("{}\n", outside_file.display())
+        );
+    }
+
+    #[test]
+    fn root_prints_the_workspace_root_from_nested_directories() {
+        let _guard = lock_process();
+        let mut shell = workspace();
+        let root = shell.mbash.current_path.clone();
+        shell.run("mkdir -p a/b");
+
+        assert_eq!(shell.run("cd a/b"), 0);
+        assert_eq!(shell.run_output("root"), format!("{}\n", root.display()));
+    }
+
+    #[test]
+    fn root_fails_outside_of_a_workspace() {
+        let mut shell = TestShell::new();
+
+        assert_eq!(shell.run("root"), 1);
+        assert!(shell.logged("Not inside an mbash workspace"));
+    }
+
+    #[test]
+    fn many_adds_are_written_in_a_single_save() {
+        let mut shell = workspace();
+        let names: Vec<String> = (0..500)
+            .map(|index| format!("file{:03}.txt", index))
+            .collect();
+        for name in &names {
+            shell.write(name, "");
+        }
+
+        for name in &names {
+            assert_eq!(shell.run(&format!("add {}", name)), 0);
+        }
+        assert_eq!(shell.read(".mtracking"), "");
+        assert_eq!(shell.mbash.tracking_files.len(), names.len());
+
+        assert_eq!(shell.run("save"), 0);
+        let expected: String = names.iter().map(|name| format!("{}\n", name)).collect();
+        assert_eq!(shell.read(".mtracking"), expected);
+    }
+
+    #[test]
+    fn add_dot_tracks_every_new_file_that_is_not_ignored() {
+        let mut shell = workspace();
+        shell.write(".mignoring", ".msnapshots/\ntarget/\n*.log\n");
+        shell.mbash.load_ignore_file();
+        shell.write("tracked.txt", "");
+        assert_eq!(shell.run("add tracked.txt"), 0);
+        for file in ["new.txt", ".env", "src/lib.rs", "target/out", "debug.log"] {
+            shell.write(file, "");
+        }
+
+        assert_eq!(shell.run_output("add ."), "Tracked 3 new files\n");
+        let mut tracked_files = shell.mbash.tracking_files.clone();
+        tracked_files.sort();
+        let mut expected: Vec<String> = [".env", "new.txt", "src/lib.rs", "tracked.txt"]
+            .iter()
+            .map(|file| tracked(&shell, file))
+            .collect();
+        expected.sort();
+        assert_eq!(tracked_files, expected);
+
+        assert_eq!(shell.run_output("add ."), "Tracked 0 new files\n");
+    }
+}
@@ -0,0 +1,184 @@
+use logger::error;
+use std::{
+    fs::{self, DirEntry},
+    io::Write,
+    path::Path,
+};
+
+use super::Mbash;
+
+#[derive(Default)]
+struct TreeSummary {
+    directories: usize,
+    files: usize,
+}
+
+impl Mbash {
+    pub(super) fn handle_tree_command(&mut self, args: &[&str]) -> i32 {
+        let mut max_depth = None;
+        let mut target = None;
+        let mut args = args.iter();
+
+        while let Some(arg) = args.next() {
+            match *arg {
+                "-L" => match args.next().and_then(|depth| depth.parse::<usize>().ok()) {
+                    Some(depth) if depth > 0 => max_depth = Some(depth),
+                    _ => {
+                        error!(
+                            self.logger,
+                            "'tree' command expects a positive depth after -L [tree [-L depth] [directory]]."
+                        );
+                        return 1;
+                    }
+                },
+                _ if target.is_none() => target = Some(*arg),
+                _ => {
+                    error!(
+                        self.logger,
+                        "'tree' command accepts a single directory [tree [-L depth] [directory]]."
+                    );
+                    return 1;
+                }
+            }
+        }
+
+        let (directory, relative_directory) = match target {
+            Some(target) => (self.resolve_path(target), Path::new(target)),
+            None => (self.current_path.clone(), Path::new("")),
+        };
+
+        if !directory.is_dir() {
+            error!(self.logger, "'{}' is not a directory.", directory.display());
+            return 1;
+        }
+
+        let mut summary = TreeSummary::default();
+        let _ = writeln!(self.output, "{}", target.unwrap_or("."));
+        let status = self.print_tree(
+            &directory,
+            relative_directory,
+            "",
+            1,
+            max_depth,
+            &mut summary,
+        );
+        let _ = writeln!(
+            self.output,
+            "\n{} directories, {} files",
+            summary.directories, summary.files
+        );
+
+        status
+    }
+
+    /// Prints the entries of `directory` with branch characters, descending until `max_depth`.
+    /// Hidden and ignored entries are left out of both the listing and the summary.
+    fn print_tree(
+        &mut self,
+        directory: &Path,
+        relative_directory: &Path,
+        indent: &str,
+        depth: usize,
+        max_depth: Option<usize>,
+        summary: &mut TreeSummary,
+    ) -> i32 {
+        let mut entries: Vec<DirEntry> = match fs::read_dir(directory) {
+            Ok(entries) => entries.filter_map(Result::ok).collect(),
+            Err(e) => {
+                error!(
+                    self.logger,
+                    "Failed to read directory '{}': '{}'.",
+                    directory.display(),
+                    e
+                );
+                return 1;
+            }
+        };
+        entries.retain(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let relative_path = relative_directory.join(&name);
+            !name.starts_with('.') && !self.is_ignored(&relative_path.to_string_lossy())
+        });
+        entries.sort_by_key(DirEntry::file_name);
+
+        let mut status = 0;
+        let entry_count = entries.len();
+        for (index, entry) in entries.into_iter().enumerate() {
+            let is_last = index + 1 == entry_count;
+            let (branch, child_indent) = if is_last {
+                ("└── ", "    ")
+            } else {
+                ("├── ", "│   ")
+            };
+
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let _ = writeln!(self.output, "{}{}{}", indent, branch, name);
+
+            let is_directory = entry.file_type().is_ok_and(|file_type| file_type.is_dir());
+            if !is_directory {
+                summary.files += 1;
+                continue;
+            }
+
+            summary.directories += 1;
+            if max_depth.is_none_or(|max_depth| depth < max_depth)
+                && self.print_tree(
+                    &entry.path(),
+                    &relative_directory.join(&name),
+                    &format!("{}{}", indent, child_indent),
+                    depth + 1,
+                    max_depth,
+                    summary,
+                ) != 0
+            {
+                status = 1;
+            }
+        }
+
+        status
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_support::TestShell;
+
+    fn shell() -> TestShell {
+        let mut shell = TestShell::new();
+        shell.write(".mignoring", "target/\n");
+        for file in ["a/x", "a/b/y", "top", "target/z"] {
+            shell.write(file, "");
+        }
+        shell.run("mkdir a/b/c");
+        shell.mbash.refresh_workspace().unwrap();
+        shell
+    }
+
+    #[test]
+    fn tree_draws_the_hierarchy_and_counts_entries() {
+        let mut shell = shell();
+
+        assert_eq!(
+            shell.run_output("tree"),
+            ".\n\
+             ├── a\n\
+             │   ├── b\n\
+             │   │   ├── c\n\
+             │   │   └── y\n\
+             │   └── x\n\
+             └── top\n\
+             \n\
+             3 directories, 3 files\n"
+        );
+    }
+
+    #[test]
+    fn tree_l_limits_the_depth() {
+        let mut shell = shell();
+
+        assert_eq!(
+            shell.run_output("tree -L 1"),
+            ".\n├── a\n└── top\n\n1 directories, 1 files\n"
+        );
+    }
+}
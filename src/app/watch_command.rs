@@ -0,0 +1,169 @@
+use logger::{debug, error};
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use std::{
+    collections::HashSet,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{atomic::Ordering, mpsc},
+    time::{Duration, SystemTime},
+};
+
+use super::Mbash;
+use crate::helper_functions;
+
+/// How often the watch loop wakes up to check whether it was interrupted
+const WATCH_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+impl Mbash {
+    pub(super) fn handle_watch_command(&mut self, _args: &[&str]) -> i32 {
+        if self.tracking_files.is_empty() {
+            error!(self.logger, "No files are being tracked, nothing to watch.");
+            return 1;
+        }
+
+        let (sender, receiver) = mpsc::channel();
+        let mut watcher = match notify::recommended_watcher(sender) {
+            Ok(watcher) => watcher,
+            Err(e) => {
+                error!(self.logger, "Failed to start watching files: '{}'.", e);
+                return 1;
+            }
+        };
+
+        // Parent directories are watched so that deleting and recreating a file keeps reporting
+        let tracked: HashSet<PathBuf> = self.tracking_files.iter().map(PathBuf::from).collect();
+        let directories: HashSet<&Path> = tracked.iter().filter_map(|path| path.parent()).collect();
+        for directory in directories {
+            if let Err(e) = watcher.watch(directory, RecursiveMode::NonRecursive) {
+                error!(
+                    self.logger,
+                    "Failed to watch '{}': '{}'.",
+                    directory.display(),
+                    e
+                );
+                return 1;
+            }
+        }
+
+        let _ = writeln!(
+            self.output,
+            "Watching {} tracked files, press Ctrl-C to stop.",
+            tracked.len()
+        );
+        let _ = self.output.flush();
+
+        self.interrupted.store(false, Ordering::Relaxed);
+        while !self.interrupted.load(Ordering::Relaxed) && !self.exiting.load(Ordering::Relaxed) {
+            match receiver.recv_timeout(WATCH_POLL_INTERVAL) {
+                Ok(Ok(event)) => self.report_watch_event(&event, &tracked),
+                Ok(Err(e)) => debug!(self.logger, "Watch error: '{}'.", e),
+                Err(mpsc::RecvTimeoutError::Timeout) => continue,
+                Err(mpsc::RecvTimeoutError::Disconnected) => break,
+            }
+        }
+
+        self.interrupted.store(false, Ordering::Relaxed);
+        debug!(self.logger, "Stopped watching tracked files.");
+        0
+    }
+
+    fn report_watch_event(&mut self, event: &Event, tracked: &HashSet<PathBuf>) {
+        let action = match event.kind {
+            EventKind::Create(_) => "created",
+            EventKind::Modify(_) => "modified",
+            EventKind::Remove(_) => "deleted",
+            EventKind::Access(_) | EventKind::Any | EventKind::Other => return,
+        };
+
+        for path in event.paths.iter().filter(|path| tracked.contains(*path)) {
+            let _ = writeln!(
+                self.output,
+                "{} {}: {}",
+                helper_functions::format_timestamp(SystemTime::now()),
+                action,
+                path.display()
+            );
+        }
+        let _ = self.output.flush();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::test_support::TestShell;
+    use super::*;
+    use notify::event::{CreateKind, ModifyKind, RemoveKind};
+    use std::{
+        fs,
+        sync::{Arc, atomic::AtomicBool},
+        thread,
+    };
+
+    #[test]
+    fn watch_events_name_tracked_files_only() {
+        let mut shell = TestShell::new();
+        let tracked_path = shell.path("notes.txt");
+        let tracked = HashSet::from([tracked_path.clone()]);
+
+        let event = |kind| Event::new(kind).add_path(tracked_path.clone());
+        shell
+            .mbash
+            .report_watch_event(&event(EventKind::Create(CreateKind::File)), &tracked);
+        shell
+            .mbash
+            .report_watch_event(&event(EventKind::Modify(ModifyKind::Any)), &tracked);
+        shell
+            .mbash
+            .report_watch_event(&event(EventKind::Remove(RemoveKind::File)), &tracked);
+        let other = Event::new(EventKind::Modify(ModifyKind::Any)).add_path(shell.path("other"));
+        shell.mbash.report_watch_event(&other, &tracked);
+
+        let output = shell.take_output();
+        let actions: Vec<&str> = output
+            .lines()
+            .map(|line| line.split_once(' ').unwrap().1)
+            .collect();
+        let path = tracked_path.display();
+        assert_eq!(
+            actions,
+            [
+                format!("created: {}", path),
+                format!("modified: {}", path),
+                format!("deleted: {}", path),
+            ]
+        );
+    }
+
+    #[test]
+    fn watch_reports_changes_until_interrupted() {
+        let mut shell = TestShell::new();
+        shell.write("notes.txt", "first");
+        shell.run("init");
+        shell.run("add notes.txt");
+        shell.take_output();
+
+        let path = shell.path("notes.txt");
+        let interrupted = Arc::clone(&shell.mbash.interrupted);
+        let finished = Arc::new(AtomicBool::new(false));
+        let modifier = {
+            let (path, finished) = (path.clone(), Arc::clone(&finished));
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(300));
+                fs::write(&path, "second").unwrap();
+                thread::sleep(Duration::from_millis(500));
+                // Stands in for Ctrl-C, repeated in case watching only just started
+                while !finished.load(Ordering::Relaxed) {
+                    interrupted.store(true, Ordering::Relaxed);
+                    thread::sleep(Duration::from_millis(50));
+                }
+            })
+        };
+
+        assert_eq!(shell.run("watch"), 0);
+        finished.store(true, Ordering::Relaxed);
+        modifier.join().unwrap();
+        let output = shell.take_output();
+        assert!(output.starts_with("Watching 1 tracked files"));
+        assert!(output.contains(&format!("modified: {}", path.display())));
+    }
+}
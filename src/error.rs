@@ -0,0 +1,16 @@
+use std::{io, path::PathBuf};
+use thiserror::Error;
+
+/// Failures that mbash reports to the code embedding it
+#[derive(Debug, Error)]
+pub enum MbashError {
+    /// The process working directory couldn't be read
+    #[error("failed to fetch the current directory: {0}")]
+    CurrentDir(#[source] io::Error),
+    /// The workspace tracking file exists but couldn't be read
+    #[error("failed to load the tracking file '{}': {source}", path.display())]
+    TrackingLoad { path: PathBuf, source: io::Error },
+    /// A command line finished with a non-zero status
+    #[error("'{command}' failed with status {status}")]
+    CommandFailed { command: String, status: i32 },
+}
@@ -0,0 +1,85 @@
+use logger::{LogLevel, Logger};
+use std::{fs::File, io::Write, time::SystemTime};
+
+use crate::helper_functions;
+
+/// Appends timestamped log lines to a file
+pub struct FileLogger {
+    file: File,
+    level: LogLevel,
+}
+
+impl FileLogger {
+    /// Logs into an already opened file, which should be opened in append mode
+    pub fn new(file: File, level: LogLevel) -> Self {
+        FileLogger { file, level }
+    }
+}
+
+impl Logger for FileLogger {
+    fn log(&self, level: LogLevel, message: &str) {
+        if severity(&level) < severity(&self.level) {
+            return;
+        }
+
+        // Logging has nowhere to report its own failures, so write errors are dropped
+        let _ = writeln!(
+            &self.file,
+            "{} [{}] {}",
+            helper_functions::format_timestamp(SystemTime::now()),
+            level_name(&level),
+            message
+        );
+    }
+}
+
+fn severity(level: &LogLevel) -> u8 {
+    match level {
+        LogLevel::DEBUG => 0,
+        LogLevel::INFO => 1,
+        LogLevel::ERROR => 2,
+    }
+}
+
+fn level_name(level: &LogLevel) -> &'static str {
+    match level {
+        LogLevel::DEBUG => "DEBUG",
+        LogLevel::INFO => "INFO",
+        LogLevel::ERROR => "ERROR",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use logger::{debug, error, info};
+    use std::fs::{self, OpenOptions};
+    use tempfile::TempDir;
+
+    #[test]
+    fn writes_timestamped_lines_at_or_above_its_level() {
+        let directory = TempDir::new().unwrap();
+        let path = directory.path().join("mbash.log");
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .unwrap();
+        let logger = FileLogger::new(file, LogLevel::INFO);
+
+        debug!(logger, "hidden");
+        info!(logger, "started");
+        error!(logger, "failed {}", 2);
+
+        let contents = fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = contents.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].ends_with(" [INFO] started"));
+        assert!(lines[1].ends_with(" [ERROR] failed 2"));
+
+        // Lines start with a timestamp like `2025-12-14T09:30:00`
+        let (timestamp, _) = lines[0].split_once(' ').unwrap();
+        assert_eq!(timestamp.len(), 19);
+        assert_eq!(&timestamp[10..11], "T");
+    }
+}
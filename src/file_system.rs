@@ -0,0 +1,471 @@
+use glob::{Pattern, PatternError};
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, MutexGuard},
+    time::SystemTime,
+};
+
+use crate::helper_functions;
+
+/// The file operations behind workspace tracking, `ls` and `rm`, so they can run against
+/// something other than the disk. The other file commands, redirects and scripts still go
+/// through `std::fs`, as does the glob expansion of command words.
+pub trait FileSystem {
+    /// Lists the paths of a directory's entries, in no particular order. Entries that can't be
+    /// read are reported one by one so the rest can still be listed.
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<io::Result<PathBuf>>>;
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>>;
+    fn read_to_string(&self, path: &Path) -> io::Result<String>;
+    /// Creates or truncates a file and opens it for writing
+    fn create(&self, path: &Path) -> io::Result<Box<dyn Write>>;
+    fn create_dir_all(&self, path: &Path) -> io::Result<()>;
+    fn remove_file(&self, path: &Path) -> io::Result<()>;
+    fn remove_dir_all(&self, path: &Path) -> io::Result<()>;
+    /// Describes a path, following symlinks
+    fn metadata(&self, path: &Path) -> io::Result<FileMetadata>;
+    /// Describes a path without following it if it's a symlink
+    fn symlink_metadata(&self, path: &Path) -> io::Result<FileMetadata>;
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf>;
+    /// Resolves a path to its absolute form with every symlink followed
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileKind {
+    File,
+    Directory,
+    Symlink,
+}
+
+/// The parts of a file's metadata mbash looks at
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileMetadata {
+    pub kind: FileKind,
+    pub len: u64,
+    pub modified: Option<SystemTime>,
+    /// Whether anyone may execute the file, always `false` outside of Unix
+    pub executable: bool,
+}
+
+impl FileMetadata {
+    pub fn is_file(&self) -> bool {
+        self.kind == FileKind::File
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.kind == FileKind::Directory
+    }
+
+    pub fn is_symlink(&self) -> bool {
+        self.kind == FileKind::Symlink
+    }
+}
+
+/// Returns the sorted paths matching an absolute glob pattern, reading directories through
+/// `file_system` one pattern component at a time
+pub(crate) fn glob(
+    file_system: &dyn FileSystem,
+    pattern: &Path,
+) -> Result<Vec<PathBuf>, PatternError> {
+    let mut paths = vec![PathBuf::new()];
+    for component in pattern.components() {
+        let text = component.as_os_str().to_string_lossy();
+        if !text.contains(['*', '?', '[']) {
+            for path in &mut paths {
+                path.push(component);
+            }
+            continue;
+        }
+
+        let component_pattern = Pattern::new(&text)?;
+        paths = paths
+            .iter()
+            .filter_map(|directory| file_system.read_dir(directory).ok())
+            .flatten()
+            .filter_map(Result::ok)
+            .filter(|path| {
+                path.file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| component_pattern.matches(name))
+            })
+            .collect();
+    }
+
+    paths.retain(|path| file_system.symlink_metadata(path).is_ok());
+    paths.sort();
+    Ok(paths)
+}
+
+/// Forwards every operation to `std::fs`
+pub struct RealFileSystem;
+
+impl FileSystem for RealFileSystem {
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<io::Result<PathBuf>>> {
+        Ok(fs::read_dir(path)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect())
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        fs::read(path)
+    }
+
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        fs::read_to_string(path)
+    }
+
+    fn create(&self, path: &Path) -> io::Result<Box<dyn Write>> {
+        Ok(Box::new(File::create(path)?))
+    }
+
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        fs::create_dir_all(path)
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        fs::remove_file(path)
+    }
+
+    fn remove_dir_all(&self, path: &Path) -> io::Result<()> {
+        fs::remove_dir_all(path)
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<FileMetadata> {
+        fs::metadata(path).map(describe_metadata)
+    }
+
+    fn symlink_metadata(&self, path: &Path) -> io::Result<FileMetadata> {
+        fs::symlink_metadata(path).map(describe_metadata)
+    }
+
+    fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+        fs::read_link(path)
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        fs::canonicalize(path)
+    }
+}
+
+fn describe_metadata(metadata: fs::Metadata) -> FileMetadata {
+    let kind = if metadata.is_symlink() {
+        FileKind::Symlink
+    } else if metadata.is_dir() {
+        FileKind::Directory
+    } else {
+        FileKind::File
+    };
+
+    FileMetadata {
+        kind,
+        len: metadata.len(),
+        modified: metadata.modified().ok(),
+        executable: is_executable(&metadata),
+    }
+}
+
+#[cfg(unix)]
+fn is_executable(metadata: &fs::Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    metadata.permissions().mode() & 0o111 != 0
+}
+
+#[cfg(not(unix))]
+fn is_executable(_metadata: &fs::Metadata) -> bool {
+    false
+}
+
+enum MemoryEntry {
+    File(Vec<u8>),
+    Directory,
+}
+
+/// Keeps files in memory, for exercising mbash without touching the disk. Clones share the same
+/// files, so a handle kept outside of `Mbash` sees what commands wrote. It has no symlinks and
+/// no modified times, which also keeps `ls` from caching its listings.
+#[derive(Clone, Default)]
+pub struct MemoryFileSystem {
+    entries: Arc<Mutex<BTreeMap<PathBuf, MemoryEntry>>>,
+}
+
+impl MemoryFileSystem {
+    pub fn new() -> Self {
+        MemoryFileSystem::default()
+    }
+
+    /// Adds a directory along with any missing parent directories
+    pub fn add_dir(&self, path: impl AsRef<Path>) {
+        let mut entries = self.lock();
+        for directory in path.as_ref().ancestors() {
+            entries
+                .entry(directory.to_path_buf())
+                .or_insert(MemoryEntry::Directory);
+        }
+    }
+
+    /// Adds or replaces a file, creating its parent directories
+    pub fn add_file(&self, path: impl AsRef<Path>, contents: impl Into<Vec<u8>>) {
+        let path = path.as_ref();
+        if let Some(parent) = path.parent() {
+            self.add_dir(parent);
+        }
+        self.lock()
+            .insert(path.to_path_buf(), MemoryEntry::File(contents.into()));
+    }
+
+    /// Returns a file's contents, or `None` if it doesn't exist or is a directory
+    pub fn contents(&self, path: impl AsRef<Path>) -> Option<Vec<u8>> {
+        match self.lock().get(path.as_ref()) {
+            Some(MemoryEntry::File(contents)) => Some(contents.clone()),
+            Some(MemoryEntry::Directory) | None => None,
+        }
+    }
+
+    pub fn exists(&self, path: impl AsRef<Path>) -> bool {
+        self.lock().contains_key(path.as_ref())
+    }
+
+    fn lock(&self) -> MutexGuard<'_, BTreeMap<PathBuf, MemoryEntry>> {
+        // Entries stay consistent even if a holder panicked, so a poisoned lock is still usable
+        self.entries
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl FileSystem for MemoryFileSystem {
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<io::Result<PathBuf>>> {
+        let entries = self.lock();
+        match entries.get(path) {
+            Some(MemoryEntry::Directory) => Ok(entries
+                .keys()
+                .filter(|entry| entry.as_path() != path && entry.parent() == Some(path))
+                .map(|entry| Ok(entry.clone()))
+                .collect()),
+            Some(MemoryEntry::File(_)) => Err(io::ErrorKind::NotADirectory.into()),
+            None => Err(io::ErrorKind::NotFound.into()),
+        }
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        match self.lock().get(path) {
+            Some(MemoryEntry::File(contents)) => Ok(contents.clone()),
+            Some(MemoryEntry::Directory) => Err(io::ErrorKind::IsADirectory.into()),
+            None => Err(io::ErrorKind::NotFound.into()),
+        }
+    }
+
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        String::from_utf8(self.read(path)?)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    fn create(&self, path: &Path) -> io::Result<Box<dyn Write>> {
+        let mut entries = self.lock();
+        let parent_exists = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => {
+                matches!(entries.get(parent), Some(MemoryEntry::Directory))
+            }
+            _ => true,
+        };
+        if !parent_exists {
+            return Err(io::ErrorKind::NotFound.into());
+        }
+        if let Some(MemoryEntry::Directory) = entries.get(path) {
+            return Err(io::ErrorKind::IsADirectory.into());
+        }
+
+        entries.insert(path.to_path_buf(), MemoryEntry::File(Vec::new()));
+        Ok(Box::new(MemoryFile {
+            file_system: self.clone(),
+            path: path.to_path_buf(),
+        }))
+    }
+
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        let blocked = path
+            .ancestors()
+            .any(|ancestor| matches!(self.lock().get(ancestor), Some(MemoryEntry::File(_))));
+        if blocked {
+            return Err(io::ErrorKind::NotADirectory.into());
+        }
+
+        self.add_dir(path);
+        Ok(())
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        let mut entries = self.lock();
+        match entries.get(path) {
+            Some(MemoryEntry::File(_)) => {
+                entries.remove(path);
+                Ok(())
+            }
+            Some(MemoryEntry::Directory) => Err(io::ErrorKind::IsADirectory.into()),
+            None => Err(io::ErrorKind::NotFound.into()),
+        }
+    }
+
+    fn remove_dir_all(&self, path: &Path) -> io::Result<()> {
+        let mut entries = self.lock();
+        match entries.get(path) {
+            Some(MemoryEntry::Directory) => {
+                entries.retain(|entry, _| !entry.starts_with(path));
+                Ok(())
+            }
+            Some(MemoryEntry::File(_)) => Err(io::ErrorKind::NotADirectory.into()),
+            None => Err(io::ErrorKind::NotFound.into()),
+        }
+    }
+
+    fn metadata(&self, path: &Path) -> io::Result<FileMetadata> {
+        match self.lock().get(path) {
+            Some(MemoryEntry::File(contents)) => Ok(FileMetadata {
+                kind: FileKind::File,
+                len: contents.len() as u64,
+                modified: None,
+                executable: false,
+            }),
+            Some(MemoryEntry::Directory) => Ok(FileMetadata {
+                kind: FileKind::Directory,
+                len: 0,
+                modified: None,
+                executable: false,
+            }),
+            None => Err(io::ErrorKind::NotFound.into()),
+        }
+    }
+
+    fn symlink_metadata(&self, path: &Path) -> io::Result<FileMetadata> {
+        self.metadata(path)
+    }
+
+    fn read_link(&self, _path: &Path) -> io::Result<PathBuf> {
+        Err(io::ErrorKind::InvalidInput.into())
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        let path = helper_functions::normalize_path(path);
+        if !self.exists(&path) {
+            return Err(io::ErrorKind::NotFound.into());
+        }
+        Ok(path)
+    }
+}
+
+/// A file opened by [`MemoryFileSystem::create`], appending each write to the stored contents
+struct MemoryFile {
+    file_system: MemoryFileSystem,
+    path: PathBuf,
+}
+
+impl Write for MemoryFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self.file_system.lock().get_mut(&self.path) {
+            Some(MemoryEntry::File(contents)) => {
+                contents.extend_from_slice(buf);
+                Ok(buf.len())
+            }
+            // The file was removed or replaced while open
+            _ => Err(io::ErrorKind::NotFound.into()),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn memory_files_are_written_through_create() {
+        let file_system = MemoryFileSystem::new();
+        file_system.add_dir("/work");
+
+        let mut file = file_system.create(Path::new("/work/a.txt")).unwrap();
+        file.write_all(b"hello ").unwrap();
+        file.write_all(b"world").unwrap();
+        assert_eq!(
+            file_system
+                .read_to_string(Path::new("/work/a.txt"))
+                .unwrap(),
+            "hello world"
+        );
+        assert_eq!(
+            file_system.metadata(Path::new("/work/a.txt")).unwrap().len,
+            11
+        );
+    }
+
+    #[test]
+    fn memory_create_needs_an_existing_parent() {
+        let file_system = MemoryFileSystem::new();
+
+        let error = file_system
+            .create(Path::new("/missing/a.txt"))
+            .err()
+            .unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn glob_matches_each_component_through_the_file_system() {
+        let file_system = MemoryFileSystem::new();
+        file_system.add_file("/work/a/notes.txt", "");
+        file_system.add_file("/work/b/todo.txt", "");
+        file_system.add_file("/work/b/image.png", "");
+        file_system.add_file("/work/c.txt", "");
+
+        assert_eq!(
+            glob(&file_system, Path::new("/work/*/*.txt")).unwrap(),
+            [
+                PathBuf::from("/work/a/notes.txt"),
+                PathBuf::from("/work/b/todo.txt")
+            ]
+        );
+        assert!(
+            glob(&file_system, Path::new("/missing/*.txt"))
+                .unwrap()
+                .is_empty()
+        );
+        assert!(glob(&file_system, Path::new("/work/[a")).is_err());
+    }
+
+    #[test]
+    fn memory_read_dir_lists_direct_children() {
+        let file_system = MemoryFileSystem::new();
+        file_system.add_file("/work/a.txt", "");
+        file_system.add_file("/work/sub/b.txt", "");
+
+        let mut entries: Vec<PathBuf> = file_system
+            .read_dir(Path::new("/work"))
+            .unwrap()
+            .into_iter()
+            .map(Result::unwrap)
+            .collect();
+        entries.sort();
+        assert_eq!(
+            entries,
+            [PathBuf::from("/work/a.txt"), PathBuf::from("/work/sub")]
+        );
+    }
+
+    #[test]
+    fn memory_remove_dir_all_removes_everything_below() {
+        let file_system = MemoryFileSystem::new();
+        file_system.add_file("/work/sub/deep/b.txt", "");
+        file_system.add_file("/work/a.txt", "");
+
+        file_system.remove_dir_all(Path::new("/work/sub")).unwrap();
+        assert!(!file_system.exists("/work/sub/deep/b.txt"));
+        assert!(!file_system.exists("/work/sub"));
+        assert!(file_system.exists("/work/a.txt"));
+    }
+}
@@ -1,18 +1,23 @@
-use logger::LogLevel;
 use logger::Logger;
 use logger::debug;
 use logger::error;
-use logger::stdout_logger::StdoutLogger;
-use std::fs::File;
+use sha2::{Digest, Sha256};
+use std::env;
+use std::fs::{self, File};
+use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-/// Creates a new file as long as it doesn't exist
-pub fn attempt_create_file(file_name: &str) -> bool {
-    let logger: Box<dyn Logger> = Box::new(StdoutLogger::new(LogLevel::DEBUG));
+#[cfg(windows)]
+const HOME_ENV_VAR: &str = "USERPROFILE";
+#[cfg(not(windows))]
+const HOME_ENV_VAR: &str = "HOME";
 
+/// Creates a new file as long as it doesn't exist, returning whether the file is now present
+pub fn attempt_create_file(file_name: &str, logger: &dyn Logger) -> bool {
     match std::fs::exists(file_name) {
         Ok(true) => {
             debug!(logger, "'{}' already exists.", file_name);
-            return true;
+            true
         }
         Ok(false) => {
             debug!(logger, "'{}' doesn't exist!", file_name);
@@ -21,10 +26,13 @@ pub fn attempt_create_file(file_name: &str) -> bool {
             let file_creation_result = File::create(file_name);
             match file_creation_result {
                 Ok(_) => debug!(logger, "Successfully created '{}' file!", file_name),
-                Err(e) => error!(logger, "Failed to create '{}' file! {}", file_name, e),
+                Err(e) => {
+                    error!(logger, "Failed to create '{}' file! {}", file_name, e);
+                    return false;
+                }
             };
 
-            return true;
+            true
         }
 
         Err(e) => {
@@ -34,7 +42,290 @@ pub fn attempt_create_file(file_name: &str) -> bool {
                 file_name,
                 e
             );
-            return false;
+            false
         }
     }
 }
+
+/// Returns the user's home directory as reported by the environment
+pub fn home_dir() -> Option<String> {
+    env::var(HOME_ENV_VAR).ok().filter(|home| !home.is_empty())
+}
+
+/// Expands a leading `~` to the user's home directory, leaving any other argument untouched
+pub fn expand_tilde(arg: &str) -> String {
+    let rest = match arg.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') || rest.starts_with('\\') => rest,
+        _ => return arg.to_string(),
+    };
+
+    match home_dir() {
+        Some(home) => format!("{}{}", home, rest),
+        None => arg.to_string(),
+    }
+}
+
+/// Lexically removes `.` and `..` components from a path without touching the filesystem
+pub fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+
+    for component in path.components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir => {
+                if !normalized.pop() {
+                    normalized.push(component);
+                }
+            }
+            _ => normalized.push(component),
+        }
+    }
+
+    normalized
+}
+
+/// Formats a byte count using the largest fitting unit, e.g. `512`, `1.2K`, `3.4M`
+pub fn human_readable_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["K", "M", "G", "T", "P"];
+
+    if bytes < 1024 {
+        return bytes.to_string();
+    }
+
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit_index = 0;
+    while size >= 1024.0 && unit_index < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit_index += 1;
+    }
+
+    format!("{:.1}{}", size, UNITS[unit_index])
+}
+
+/// Formats an elapsed time in milliseconds below a second and in seconds above, e.g. `12.34ms`, `1.50s`
+pub fn human_readable_duration(duration: Duration) -> String {
+    if duration.as_secs() == 0 {
+        format!("{:.2}ms", duration.as_secs_f64() * 1000.0)
+    } else {
+        format!("{:.2}s", duration.as_secs_f64())
+    }
+}
+
+/// Formats a point in time as an ISO-8601 UTC timestamp, e.g. `2025-12-14T09:30:00`
+pub fn format_timestamp(time: SystemTime) -> String {
+    let seconds = match time.duration_since(UNIX_EPOCH) {
+        Ok(duration) => duration.as_secs() as i64,
+        Err(e) => -(e.duration().as_secs() as i64),
+    };
+
+    let days = seconds.div_euclid(86_400);
+    let seconds_of_day = seconds.rem_euclid(86_400);
+
+    // Converts days since the epoch to a civil date, see http://howardhinnant.github.io/date_algorithms.html
+    let shifted_days = days + 719_468;
+    let era = shifted_days.div_euclid(146_097);
+    let day_of_era = shifted_days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        seconds_of_day / 3_600,
+        seconds_of_day % 3_600 / 60,
+        seconds_of_day % 60
+    )
+}
+
+/// Returns the hex encoded SHA-256 digest of a file's contents
+pub fn hash_contents(contents: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(contents))
+}
+
+/// Searches the directories in `PATH` for an executable called `name`.
+/// On Windows every extension listed in `PATHEXT` is tried as well.
+pub fn find_executable(name: &str) -> Option<PathBuf> {
+    let path_var = env::var_os("PATH")?;
+
+    env::split_paths(&path_var).find_map(|directory| {
+        executable_candidates(name)
+            .into_iter()
+            .map(|candidate| directory.join(candidate))
+            .find(|candidate| is_executable(candidate))
+    })
+}
+
+/// Lists the executables in `PATH` whose names start with `prefix`, as the rest of the name
+/// (without a Windows extension) and the path of the first one found, sorted by name
+pub fn find_executables_with_prefix(prefix: &str) -> Vec<(String, PathBuf)> {
+    let Some(path_var) = env::var_os("PATH") else {
+        return Vec::new();
+    };
+
+    let mut executables: Vec<(String, PathBuf)> = Vec::new();
+    for directory in env::split_paths(&path_var) {
+        let Ok(entries) = fs::read_dir(&directory) else {
+            continue;
+        };
+
+        for entry in entries.filter_map(Result::ok) {
+            let path = entry.path();
+            // Windows executables carry an extension, like `mbash-foo.exe`
+            let file_name = if cfg!(windows) {
+                path.file_stem()
+            } else {
+                path.file_name()
+            };
+            let Some(name) = file_name
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.strip_prefix(prefix))
+            else {
+                continue;
+            };
+
+            let name = name.to_string();
+            if !name.is_empty()
+                && is_executable(&path)
+                && !executables.iter().any(|(known, _)| *known == name)
+            {
+                executables.push((name, path));
+            }
+        }
+    }
+
+    executables.sort();
+    executables
+}
+
+#[cfg(windows)]
+fn executable_candidates(name: &str) -> Vec<String> {
+    let extensions = env::var("PATHEXT").unwrap_or_else(|_| String::from(".COM;.EXE;.BAT;.CMD"));
+
+    std::iter::once(name.to_string())
+        .chain(
+            extensions
+                .split(';')
+                .filter(|extension| !extension.is_empty())
+                .map(|extension| format!("{}{}", name, extension)),
+        )
+        .collect()
+}
+
+#[cfg(not(windows))]
+fn executable_candidates(name: &str) -> Vec<String> {
+    vec![name.to_string()]
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    fs::metadata(path)
+        .is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use logger::LogLevel;
+    use std::cell::RefCell;
+    use tempfile::TempDir;
+
+    #[derive(Default)]
+    struct CapturingLogger {
+        messages: RefCell<Vec<String>>,
+    }
+
+    impl Logger for CapturingLogger {
+        fn log(&self, _level: LogLevel, message: &str) {
+            self.messages.borrow_mut().push(message.to_string());
+        }
+    }
+
+    #[test]
+    fn create_file_logs_through_the_given_logger() {
+        let directory = TempDir::new().unwrap();
+        let path = directory.path().join("created.txt");
+        let path = path.to_str().unwrap();
+        let logger = CapturingLogger::default();
+
+        assert!(attempt_create_file(path, &logger));
+        assert!(Path::new(path).is_file());
+        assert!(attempt_create_file(path, &logger));
+
+        let messages = logger.messages.borrow();
+        assert!(messages.contains(&format!("Successfully created '{}' file!", path)));
+        assert_eq!(
+            messages.last(),
+            Some(&format!("'{}' already exists.", path))
+        );
+    }
+
+    #[test]
+    fn create_file_reports_failures() {
+        let directory = TempDir::new().unwrap();
+        let path = directory.path().join("missing").join("file.txt");
+        let logger = CapturingLogger::default();
+
+        assert!(!attempt_create_file(path.to_str().unwrap(), &logger));
+        assert!(
+            logger
+                .messages
+                .borrow()
+                .iter()
+                .any(|message| message.starts_with("Failed to create"))
+        );
+    }
+
+    #[test]
+    fn sizes_use_the_largest_fitting_unit() {
+        assert_eq!(human_readable_size(512), "512");
+        assert_eq!(human_readable_size(1_229), "1.2K");
+        assert_eq!(human_readable_size(3_565_158), "3.4M");
+    }
+
+    #[test]
+    fn durations_use_milliseconds_below_a_second() {
+        assert_eq!(
+            human_readable_duration(Duration::from_micros(1_500)),
+            "1.50ms"
+        );
+        assert_eq!(
+            human_readable_duration(Duration::from_millis(2_250)),
+            "2.25s"
+        );
+    }
+
+    #[test]
+    fn timestamps_are_iso_8601() {
+        assert_eq!(format_timestamp(UNIX_EPOCH), "1970-01-01T00:00:00");
+        assert_eq!(
+            format_timestamp(UNIX_EPOCH + Duration::from_secs(1_765_704_600)),
+            "2025-12-14T09:30:00"
+        );
+    }
+
+    #[test]
+    fn contents_hash_to_hex_sha256() {
+        assert_eq!(
+            hash_contents(b"abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+}
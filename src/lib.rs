@@ -0,0 +1,14 @@
+mod app;
+mod error;
+mod file_logger;
+mod file_system;
+mod helper_functions;
+mod line_editor;
+mod output_sink;
+mod parser;
+
+pub use app::{CommandHandler, Mbash};
+pub use error::MbashError;
+pub use file_logger::FileLogger;
+pub use file_system::{FileKind, FileMetadata, FileSystem, MemoryFileSystem, RealFileSystem};
+pub use output_sink::OutputSink;
@@ -0,0 +1,155 @@
+use rustyline::{
+    Context, Helper, completion::Completer, highlight::Highlighter, hint::Hinter,
+    validate::Validator,
+};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Line editing helper that completes command names and file names
+#[derive(Default)]
+pub struct MbashHelper {
+    current_path: PathBuf,
+    command_names: Vec<String>,
+}
+
+impl MbashHelper {
+    /// Refreshes the state completions are computed from, called before reading each line
+    pub fn update(&mut self, current_path: &Path, command_names: Vec<String>) {
+        self.current_path = current_path.to_path_buf();
+        self.command_names = command_names;
+    }
+}
+
+impl Completer for MbashHelper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        Ok(complete(line, pos, &self.current_path, &self.command_names))
+    }
+}
+
+impl Hinter for MbashHelper {
+    type Hint = String;
+}
+
+impl Highlighter for MbashHelper {}
+
+impl Validator for MbashHelper {}
+
+impl Helper for MbashHelper {}
+
+/// Returns the start of the word under the cursor along with its sorted completion candidates.
+/// The first word completes against command names, any other word against file names.
+pub fn complete(
+    line: &str,
+    pos: usize,
+    current_path: &Path,
+    command_names: &[String],
+) -> (usize, Vec<String>) {
+    let before_cursor = &line[..pos];
+    let start = before_cursor
+        .char_indices()
+        .rev()
+        .find(|(_, c)| c.is_whitespace())
+        .map(|(index, c)| index + c.len_utf8())
+        .unwrap_or(0);
+    let word = &before_cursor[start..];
+
+    let mut candidates: Vec<String> = if before_cursor[..start].trim().is_empty() {
+        command_names
+            .iter()
+            .filter(|name| name.starts_with(word))
+            .cloned()
+            .collect()
+    } else {
+        complete_file_name(word, current_path)
+    };
+
+    candidates.sort();
+    candidates.dedup();
+    (start, candidates)
+}
+
+fn complete_file_name(word: &str, current_path: &Path) -> Vec<String> {
+    let (directory_part, name_prefix) = match word.rfind('/') {
+        Some(index) => word.split_at(index + 1),
+        None => ("", word),
+    };
+
+    let Ok(entries) = fs::read_dir(current_path.join(directory_part)) else {
+        return Vec::new();
+    };
+
+    entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if !name.starts_with(name_prefix) || (name.starts_with('.') && name_prefix.is_empty()) {
+                return None;
+            }
+
+            let suffix = match entry.file_type() {
+                Ok(file_type) if file_type.is_dir() => "/",
+                _ => "",
+            };
+            Some(format!("{}{}{}", directory_part, name, suffix))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn first_word_completes_command_names() {
+        let commands = names(&["cd", "cat", "ls"]);
+
+        assert_eq!(
+            complete("c", 1, Path::new("."), &commands),
+            (0, names(&["cat", "cd"]))
+        );
+        assert_eq!(
+            complete("  l", 3, Path::new("."), &commands),
+            (2, names(&["ls"]))
+        );
+    }
+
+    #[test]
+    fn later_words_complete_file_names() {
+        let directory = TempDir::new().unwrap();
+        fs::write(directory.path().join("notes.txt"), "").unwrap();
+        fs::write(directory.path().join("nothing.md"), "").unwrap();
+        fs::write(directory.path().join(".notes"), "").unwrap();
+        fs::create_dir_all(directory.path().join("nested/inner")).unwrap();
+
+        assert_eq!(
+            complete("cat no", 6, directory.path(), &[]),
+            (4, names(&["notes.txt", "nothing.md"]))
+        );
+        assert_eq!(
+            complete("cd n", 4, directory.path(), &[]),
+            (3, names(&["nested/", "notes.txt", "nothing.md"]))
+        );
+        assert_eq!(
+            complete("cd nested/i", 11, directory.path(), &[]),
+            (3, names(&["nested/inner/"]))
+        );
+        assert_eq!(
+            complete("cat .no", 7, directory.path(), &[]),
+            (4, names(&[".notes"]))
+        );
+    }
+}
@@ -1,13 +1,167 @@
-mod app;
-mod helper_functions;
-
-use app::Mbash;
 use logger::{LogLevel, Logger, stdout_logger::StdoutLogger};
+use mbash::{FileLogger, Mbash};
+use std::{env, fs::OpenOptions, path::Path, process::ExitCode};
+
+const USAGE_EXIT_CODE: u8 = 2;
+const LOG_FILE_ENV_VAR: &str = "MBASH_LOG_FILE";
+const LOG_LEVEL_ENV_VAR: &str = "MBASH_LOG_LEVEL";
+const DEFAULT_LOG_LEVEL: LogLevel = LogLevel::INFO;
+
+enum Mode {
+    Interactive,
+    Version,
+    Command(String),
+    Script(String),
+}
+
+struct Options {
+    mode: Mode,
+    log_file: Option<String>,
+}
+
+fn parse_options(args: &[String]) -> Result<Options, String> {
+    let mut options = Options {
+        mode: Mode::Interactive,
+        log_file: env::var(LOG_FILE_ENV_VAR).ok(),
+    };
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--version" => {
+                options.mode = Mode::Version;
+                break;
+            }
+            "--log-file" => match args.next() {
+                Some(log_file) => options.log_file = Some(log_file.clone()),
+                None => return Err(String::from("--log-file requires a path")),
+            },
+            "-c" => match args.next() {
+                Some(command_line) => options.mode = Mode::Command(command_line.clone()),
+                None => {
+                    return Err(String::from(
+                        "-c requires a command [mbash -c \"<command>\"]",
+                    ));
+                }
+            },
+            script_path => {
+                options.mode = Mode::Script(script_path.to_string());
+                break;
+            }
+        }
+    }
+
+    Ok(options)
+}
+
+/// Parses a log level name case-insensitively, e.g. `debug`, `INFO` or `Error`
+fn parse_log_level(level: &str) -> Option<LogLevel> {
+    match level.trim().to_ascii_uppercase().as_str() {
+        "DEBUG" => Some(LogLevel::DEBUG),
+        "INFO" => Some(LogLevel::INFO),
+        "ERROR" => Some(LogLevel::ERROR),
+        _ => None,
+    }
+}
+
+fn log_level_from_env() -> LogLevel {
+    let Ok(level) = env::var(LOG_LEVEL_ENV_VAR) else {
+        return DEFAULT_LOG_LEVEL;
+    };
+
+    parse_log_level(&level).unwrap_or_else(|| {
+        eprintln!(
+            "mbash: unknown {} '{}', expected DEBUG, INFO or ERROR",
+            LOG_LEVEL_ENV_VAR, level
+        );
+        DEFAULT_LOG_LEVEL
+    })
+}
+
+fn create_logger(log_file: Option<&str>, level: LogLevel) -> Box<dyn Logger> {
+    let Some(log_file) = log_file else {
+        return Box::new(StdoutLogger::new(level));
+    };
 
-fn main() {
-    let logger: Box<dyn Logger> = Box::new(StdoutLogger::new(LogLevel::DEBUG));
+    match OpenOptions::new().create(true).append(true).open(log_file) {
+        Ok(file) => Box::new(FileLogger::new(file, level)),
+        Err(e) => {
+            eprintln!(
+                "mbash: failed to open log file '{}', logging to stdout instead: {}",
+                log_file, e
+            );
+            Box::new(StdoutLogger::new(level))
+        }
+    }
+}
+
+/// Converts a shell status into a process exit code, keeping the low byte like POSIX shells do
+fn exit_code(status: i32) -> ExitCode {
+    ExitCode::from((status & 0xff) as u8)
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let options = match parse_options(&args) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("mbash: {}", e);
+            return ExitCode::from(USAGE_EXIT_CODE);
+        }
+    };
+
+    if let Mode::Version = options.mode {
+        println!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+        return ExitCode::SUCCESS;
+    }
+
+    let logger = create_logger(options.log_file.as_deref(), log_level_from_env());
     let mut mbash = Mbash::new(logger);
-    mbash.setup();
+    if let Err(e) = mbash.setup() {
+        eprintln!("mbash: failed to start: {}", e);
+        return ExitCode::FAILURE;
+    }
+
+    match options.mode {
+        Mode::Command(command_line) => mbash.execute_line(&command_line),
+        Mode::Script(script_path) => {
+            if let Err(e) = mbash.run_script(Path::new(&script_path)) {
+                eprintln!("mbash: failed to run script '{}': {}", script_path, e);
+                mbash.save_tracking_changes();
+                return ExitCode::FAILURE;
+            }
+        }
+        Mode::Interactive => mbash.run(),
+        Mode::Version => unreachable!("--version exits before the shell starts"),
+    }
+
+    mbash.save_tracking_changes();
+    exit_code(mbash.last_exit_code())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn log_levels_parse_case_insensitively() {
+        assert!(matches!(parse_log_level("debug"), Some(LogLevel::DEBUG)));
+        assert!(matches!(parse_log_level("Info"), Some(LogLevel::INFO)));
+        assert!(matches!(parse_log_level(" ERROR\n"), Some(LogLevel::ERROR)));
+        assert!(parse_log_level("verbose").is_none());
+    }
+
+    #[test]
+    fn log_level_defaults_to_info() {
+        // SAFETY: this is the only test reading or changing the environment
+        unsafe { env::remove_var(LOG_LEVEL_ENV_VAR) };
+        assert!(matches!(log_level_from_env(), LogLevel::INFO));
+
+        unsafe { env::set_var(LOG_LEVEL_ENV_VAR, "loud") };
+        assert!(matches!(log_level_from_env(), LogLevel::INFO));
 
-    let _ = mbash.run();
-}
\ No newline at end of file
+        unsafe { env::set_var(LOG_LEVEL_ENV_VAR, "error") };
+        assert!(matches!(log_level_from_env(), LogLevel::ERROR));
+        unsafe { env::remove_var(LOG_LEVEL_ENV_VAR) };
+    }
+}
@@ -0,0 +1,70 @@
+use std::{
+    fs::File,
+    io::{self, Write},
+};
+
+/// Destination for the output of builtin commands
+pub enum OutputSink {
+    Stdout,
+    Buffer(Vec<u8>),
+    File(File),
+    Writer(Box<dyn Write>),
+}
+
+impl OutputSink {
+    pub fn is_stdout(&self) -> bool {
+        matches!(self, OutputSink::Stdout)
+    }
+
+    /// Returns the captured bytes, which are empty for sinks that don't buffer
+    pub fn into_buffer(self) -> Vec<u8> {
+        match self {
+            OutputSink::Buffer(buffer) => buffer,
+            OutputSink::Stdout | OutputSink::File(_) | OutputSink::Writer(_) => Vec::new(),
+        }
+    }
+}
+
+impl Write for OutputSink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            OutputSink::Stdout => io::stdout().write(buf),
+            OutputSink::Buffer(buffer) => buffer.write(buf),
+            OutputSink::File(file) => file.write(buf),
+            OutputSink::Writer(writer) => writer.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            OutputSink::Stdout => io::stdout().flush(),
+            OutputSink::Buffer(_) => Ok(()),
+            OutputSink::File(file) => file.flush(),
+            OutputSink::Writer(writer) => writer.flush(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buffers_keep_what_was_written() {
+        let mut sink = OutputSink::Buffer(Vec::new());
+        write!(sink, "one ").unwrap();
+        writeln!(sink, "two").unwrap();
+
+        assert!(!sink.is_stdout());
+        assert_eq!(sink.into_buffer(), b"one two\n");
+    }
+
+    #[test]
+    fn other_sinks_have_no_buffer() {
+        let mut sink = OutputSink::Writer(Box::new(io::sink()));
+        writeln!(sink, "dropped").unwrap();
+
+        assert!(sink.into_buffer().is_empty());
+        assert!(OutputSink::Stdout.is_stdout());
+    }
+}
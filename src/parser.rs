@@ -0,0 +1,898 @@
+use glob::{MatchOptions, Pattern};
+use std::fmt;
+use std::path::Path;
+
+use crate::helper_functions;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Operator {
+    Pipe,
+    RedirectOutput,
+    RedirectAppend,
+    RedirectInput,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Token {
+    Word(String),
+    Operator(Operator),
+}
+
+impl fmt::Display for Operator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operator::Pipe => write!(f, "|"),
+            Operator::RedirectOutput => write!(f, ">"),
+            Operator::RedirectAppend => write!(f, ">>"),
+            Operator::RedirectInput => write!(f, "<"),
+        }
+    }
+}
+
+pub struct OutputRedirect {
+    pub path: String,
+    pub append: bool,
+}
+
+/// A single command of a pipeline along with its redirections
+#[derive(Default)]
+pub struct PipelineStage {
+    pub words: Vec<String>,
+    pub output_redirect: Option<OutputRedirect>,
+    pub input_redirect: Option<String>,
+}
+
+/// Decides whether a command in a list runs, based on the exit code of the one before it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Connector {
+    Always,
+    IfSuccess,
+    IfFailure,
+}
+
+impl fmt::Display for Connector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Connector::Always => write!(f, ";"),
+            Connector::IfSuccess => write!(f, "&&"),
+            Connector::IfFailure => write!(f, "||"),
+        }
+    }
+}
+
+/// A command of a command list, with the connector deciding whether it runs
+pub struct ListSegment {
+    pub connector: Connector,
+    pub text: String,
+    /// Set when the segment ends with `&`, so it runs without being waited for
+    pub background: bool,
+}
+
+/// Splits a command line on unquoted `;`, `&`, `&&` and `||`, keeping each segment's raw text so
+/// it can be expanded right before it runs and observe the exit code of the previous segment.
+pub fn split_command_list(input: &str) -> Result<Vec<ListSegment>, String> {
+    let mut segments = Vec::new();
+    let mut segment = String::new();
+    let mut connector = Connector::Always;
+    let mut quote: Option<char> = None;
+    let mut chars = input.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        let mut background = false;
+        let next_connector = match (quote, c) {
+            (Some(open), c) if c == open => {
+                quote = None;
+                None
+            }
+            (Some('\''), _) => None,
+            (_, '\\') => {
+                segment.push(c);
+                if let Some(escaped) = chars.next() {
+                    segment.push(escaped);
+                }
+                continue;
+            }
+            (Some(_), _) => None,
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                None
+            }
+            (None, ';') => Some(Connector::Always),
+            (None, '&') if chars.next_if_eq(&'&').is_some() => Some(Connector::IfSuccess),
+            (None, '&') => {
+                background = true;
+                Some(Connector::Always)
+            }
+            (None, '|') if chars.next_if_eq(&'|').is_some() => Some(Connector::IfFailure),
+            _ => None,
+        };
+
+        match next_connector {
+            Some(next_connector) => {
+                if segment.trim().is_empty() {
+                    let operator = if background {
+                        String::from("&")
+                    } else {
+                        next_connector.to_string()
+                    };
+                    return Err(format!("syntax error near '{}'", operator));
+                }
+                segments.push(ListSegment {
+                    connector,
+                    text: std::mem::take(&mut segment),
+                    background,
+                });
+                connector = next_connector;
+            }
+            None => segment.push(c),
+        }
+    }
+
+    if !segment.trim().is_empty() {
+        segments.push(ListSegment {
+            connector,
+            text: segment,
+            background: false,
+        });
+    } else if connector != Connector::Always {
+        return Err(format!("syntax error near '{}'", connector));
+    }
+
+    Ok(segments)
+}
+
+/// Whether `input` has a `|`, `&`, `;`, `<` or `>` outside of quotes and escapes, which
+/// would make it more than a single command
+pub fn has_unquoted_operator(input: &str) -> bool {
+    let mut quote: Option<char> = None;
+    let mut chars = input.chars();
+
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(open), c) if c == open => quote = None,
+            (Some('\''), _) => {}
+            (_, '\\') => {
+                chars.next();
+            }
+            (Some(_), _) => {}
+            (None, '\'' | '"') => quote = Some(c),
+            (None, '|' | '&' | ';' | '<' | '>') => return true,
+            _ => {}
+        }
+    }
+
+    false
+}
+
+/// Why a command line can't run yet and goes on with the next line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Continuation {
+    /// The line ends with a `\` outside of single quotes, which joins it with the next line
+    Backslash,
+    /// A quote is still open, so the next line is part of the quoted text
+    Quote,
+}
+
+/// Tells whether a command line is incomplete and how it continues
+pub fn continuation(input: &str) -> Option<Continuation> {
+    let mut quote: Option<char> = None;
+    let mut trailing_backslash = false;
+    let mut chars = input.chars();
+
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(open), c) if c == open => quote = None,
+            (Some('\''), _) => {}
+            (_, '\\') => trailing_backslash = chars.next().is_none(),
+            (Some(_), _) => {}
+            (None, '\'' | '"') => quote = Some(c),
+            _ => {}
+        }
+    }
+
+    if trailing_backslash {
+        Some(Continuation::Backslash)
+    } else if quote.is_some() {
+        Some(Continuation::Quote)
+    } else {
+        None
+    }
+}
+
+/// Appends the next line to an incomplete command line, dropping a joining backslash
+pub fn continue_line(command_line: &mut String, continuation: Continuation, line: &str) {
+    match continuation {
+        Continuation::Backslash => {
+            command_line.pop();
+        }
+        Continuation::Quote => command_line.push('\n'),
+    }
+    command_line.push_str(line);
+}
+
+/// The most words a single word may expand to, so a huge range fails instead of exhausting memory
+const MAX_BRACE_WORDS: usize = 10_000;
+
+/// Expands unquoted `{a,b}` lists and `{1..3}` ranges in every word of a command line, before
+/// any other expansion. Lists may nest and quoted or escaped braces are kept literally.
+/// Fails when a word would expand to more than `MAX_BRACE_WORDS` words.
+pub fn expand_braces(input: &str) -> Result<String, String> {
+    let mut result = String::with_capacity(input.len());
+    let mut word = String::new();
+    let mut quote: Option<char> = None;
+    let mut chars = input.chars();
+
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(open), c) if c == open => quote = None,
+            (Some('\''), _) => {}
+            (_, '\\') => {
+                word.push(c);
+                if let Some(escaped) = chars.next() {
+                    word.push(escaped);
+                }
+                continue;
+            }
+            (Some(_), _) => {}
+            (None, '\'' | '"') => quote = Some(c),
+            (None, c) if c.is_whitespace() || matches!(c, '|' | '>' | '<') => {
+                result.push_str(&expand_word_braces(&word)?.join(" "));
+                word.clear();
+                result.push(c);
+                continue;
+            }
+            _ => {}
+        }
+        word.push(c);
+    }
+
+    result.push_str(&expand_word_braces(&word)?.join(" "));
+    Ok(result)
+}
+
+fn expand_word_braces(word: &str) -> Result<Vec<String>, String> {
+    let chars: Vec<char> = word.chars().collect();
+    let active = active_characters(&chars);
+
+    for open in 0..chars.len() {
+        if !active[open] || chars[open] != '{' || (open > 0 && chars[open - 1] == '$') {
+            continue;
+        }
+        let Some(close) = matching_brace(&chars, &active, open) else {
+            continue;
+        };
+        let Some(alternatives) =
+            brace_alternatives(&chars[open + 1..close], &active[open + 1..close])?
+        else {
+            continue;
+        };
+
+        let prefix: String = chars[..open].iter().collect();
+        let suffix: String = chars[close + 1..].iter().collect();
+        let mut words = Vec::new();
+        for alternative in alternatives {
+            words.extend(expand_word_braces(&format!(
+                "{prefix}{alternative}{suffix}"
+            ))?);
+            if words.len() > MAX_BRACE_WORDS {
+                return Err(too_many_brace_words(word));
+            }
+        }
+        return Ok(words);
+    }
+
+    Ok(vec![word.to_string()])
+}
+
+fn too_many_brace_words(word: &str) -> String {
+    format!("'{}' expands to more than {} words", word, MAX_BRACE_WORDS)
+}
+
+/// Marks the characters of a raw word that are neither quoted nor escaped
+fn active_characters(chars: &[char]) -> Vec<bool> {
+    let mut active = vec![false; chars.len()];
+    let mut quote: Option<char> = None;
+    let mut escaped = false;
+
+    for (i, &c) in chars.iter().enumerate() {
+        match (quote, c) {
+            _ if escaped => escaped = false,
+            (Some(open), c) if c == open => quote = None,
+            (Some('\''), _) => {}
+            (_, '\\') => escaped = true,
+            (Some(_), _) => {}
+            (None, '\'' | '"') => quote = Some(c),
+            (None, _) => active[i] = true,
+        }
+    }
+
+    active
+}
+
+fn matching_brace(chars: &[char], active: &[bool], open: usize) -> Option<usize> {
+    let mut depth = 0;
+    for i in open..chars.len() {
+        if !active[i] {
+            continue;
+        }
+        match chars[i] {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Splits the inside of a brace pair on its top level commas, or expands it as a `start..end`
+/// range of numbers or letters. Returns `None` when the braces should be kept literally.
+fn brace_alternatives(body: &[char], active: &[bool]) -> Result<Option<Vec<String>>, String> {
+    let mut alternatives = Vec::new();
+    let mut alternative = String::new();
+    let mut depth = 0;
+
+    for (&c, &is_active) in body.iter().zip(active) {
+        match c {
+            '{' if is_active => depth += 1,
+            '}' if is_active => depth -= 1,
+            ',' if is_active && depth == 0 => {
+                alternatives.push(std::mem::take(&mut alternative));
+                continue;
+            }
+            _ => {}
+        }
+        alternative.push(c);
+    }
+
+    if !alternatives.is_empty() {
+        alternatives.push(alternative);
+        return Ok(Some(alternatives));
+    }
+
+    let Some((start, end)) = alternative.split_once("..") else {
+        return Ok(None);
+    };
+    if let (Ok(start), Ok(end)) = (start.parse::<i64>(), end.parse::<i64>()) {
+        if start.abs_diff(end) >= MAX_BRACE_WORDS as u64 {
+            return Err(too_many_brace_words(&format!("{{{}}}", alternative)));
+        }
+        let values: Vec<i64> = if start <= end {
+            (start..=end).collect()
+        } else {
+            (end..=start).rev().collect()
+        };
+        return Ok(Some(values.iter().map(i64::to_string).collect()));
+    }
+
+    let mut range_chars = start.chars().chain(end.chars());
+    match (range_chars.next(), range_chars.next(), range_chars.next()) {
+        (Some(start_char), Some(end_char), None)
+            if start.len() == 1
+                && start_char.is_ascii_alphabetic()
+                && end_char.is_ascii_alphabetic() =>
+        {
+            let letters: Vec<char> = if start_char <= end_char {
+                (start_char..=end_char).collect()
+            } else {
+                (end_char..=start_char).rev().collect()
+            };
+            Ok(Some(letters.iter().map(char::to_string).collect()))
+        }
+        _ => Ok(None),
+    }
+}
+
+/// Splits a command line into words and operators, honoring single quotes, double quotes and
+/// backslash escapes. Variables are looked up with `variable` and expanded outside of single
+/// quotes, and a leading `~` is expanded when unquoted. Words with unquoted `*`, `?` or `[` are
+/// replaced by the sorted paths they match under `current_path`, or kept literally when nothing
+/// matches.
+pub fn tokenize(
+    input: &str,
+    last_exit_code: i32,
+    variable: &dyn Fn(&str) -> Option<String>,
+    current_path: &Path,
+) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut word = WordBuilder::default();
+    let mut pending = String::new();
+    let mut chars = input.chars().peekable();
+
+    // Expands the unquoted text collected so far and appends it to the current word
+    let flush = |word: &mut WordBuilder, pending: &mut String| {
+        if pending.is_empty() {
+            return;
+        }
+
+        let text = if word.text.is_empty() {
+            helper_functions::expand_tilde(pending)
+        } else {
+            pending.clone()
+        };
+        word.push_unquoted(&expand_variables(&text, last_exit_code, variable));
+        pending.clear();
+    };
+
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {
+                flush(&mut word, &mut pending);
+                word.finish(&mut tokens, current_path);
+            }
+            '|' | '>' | '<' => {
+                flush(&mut word, &mut pending);
+                word.finish(&mut tokens, current_path);
+
+                let operator = match c {
+                    '>' if chars.next_if_eq(&'>').is_some() => Operator::RedirectAppend,
+                    '>' => Operator::RedirectOutput,
+                    '<' => Operator::RedirectInput,
+                    _ => Operator::Pipe,
+                };
+                tokens.push(Token::Operator(operator));
+            }
+            '\\' => {
+                flush(&mut word, &mut pending);
+                match chars.next() {
+                    Some(escaped) => word.push_quoted(&escaped.to_string()),
+                    None => word.push_quoted("\\"),
+                }
+            }
+            '\'' => {
+                flush(&mut word, &mut pending);
+                let mut quoted = String::new();
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(other) => quoted.push(other),
+                        None => return Err(String::from("unterminated single quote")),
+                    }
+                }
+                word.push_quoted(&quoted);
+            }
+            '"' => {
+                flush(&mut word, &mut pending);
+                let mut quoted = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(escaped @ ('"' | '\\' | '$')) => {
+                                quoted = expand_variables(&quoted, last_exit_code, variable);
+                                word.push_quoted(&quoted);
+                                word.push_quoted(&escaped.to_string());
+                                quoted.clear();
+                            }
+                            Some(other) => {
+                                quoted.push('\\');
+                                quoted.push(other);
+                            }
+                            None => return Err(String::from("unterminated double quote")),
+                        },
+                        Some(other) => quoted.push(other),
+                        None => return Err(String::from("unterminated double quote")),
+                    }
+                }
+                word.push_quoted(&expand_variables(&quoted, last_exit_code, variable));
+            }
+            _ => {
+                word.started = true;
+                pending.push(c);
+            }
+        }
+    }
+
+    flush(&mut word, &mut pending);
+    word.finish(&mut tokens, current_path);
+
+    Ok(tokens)
+}
+
+/// A word being tokenized, along with a glob pattern in which its quoted parts are escaped
+#[derive(Default)]
+struct WordBuilder {
+    text: String,
+    pattern: String,
+    has_glob: bool,
+    started: bool,
+}
+
+impl WordBuilder {
+    fn push_quoted(&mut self, text: &str) {
+        self.started = true;
+        self.text.push_str(text);
+        self.pattern.push_str(&Pattern::escape(text));
+    }
+
+    fn push_unquoted(&mut self, text: &str) {
+        self.started = true;
+        self.text.push_str(text);
+        self.pattern.push_str(text);
+        self.has_glob |= text.contains(['*', '?', '[']);
+    }
+
+    fn finish(&mut self, tokens: &mut Vec<Token>, current_path: &Path) {
+        if !self.started {
+            return;
+        }
+
+        let word = std::mem::take(self);
+        let matches = if word.has_glob {
+            expand_glob(&word.pattern, current_path)
+        } else {
+            Vec::new()
+        };
+
+        if matches.is_empty() {
+            tokens.push(Token::Word(word.text));
+        } else {
+            tokens.extend(matches.into_iter().map(Token::Word));
+        }
+    }
+}
+
+/// Returns the sorted paths matching `pattern`, relative to `current_path` unless the pattern
+/// is absolute. Like a shell, wildcards don't match a leading `.` unless it's written out.
+fn expand_glob(pattern: &str, current_path: &Path) -> Vec<String> {
+    let is_absolute = Path::new(pattern).is_absolute();
+    let full_pattern = if is_absolute {
+        pattern.to_string()
+    } else {
+        let base = Pattern::escape(&current_path.to_string_lossy());
+        format!("{}/{}", base.trim_end_matches('/'), pattern)
+    };
+
+    let options = MatchOptions {
+        require_literal_leading_dot: true,
+        ..MatchOptions::new()
+    };
+    let Ok(paths) = glob::glob_with(&full_pattern, options) else {
+        return Vec::new();
+    };
+
+    let mut matches: Vec<String> = paths
+        .filter_map(Result::ok)
+        .map(|path| {
+            let shown_path = if is_absolute {
+                &path
+            } else {
+                path.strip_prefix(current_path).unwrap_or(&path)
+            };
+            shown_path.to_string_lossy().into_owned()
+        })
+        .collect();
+    matches.sort();
+    matches
+}
+
+/// Groups tokens into pipeline stages, pulling redirection targets out of each stage's words
+pub fn parse_pipeline(tokens: Vec<Token>) -> Result<Vec<PipelineStage>, String> {
+    let mut stages = Vec::new();
+    let mut current = PipelineStage::default();
+    let mut tokens = tokens.into_iter();
+
+    while let Some(token) = tokens.next() {
+        match token {
+            Token::Word(word) => current.words.push(word),
+            Token::Operator(Operator::Pipe) => {
+                if current.words.is_empty() {
+                    return Err(format!("syntax error near '{}'", Operator::Pipe));
+                }
+                stages.push(std::mem::take(&mut current));
+            }
+            Token::Operator(operator) => {
+                let Some(Token::Word(path)) = tokens.next() else {
+                    return Err(format!("expected a file name after '{}'", operator));
+                };
+                if operator == Operator::RedirectInput {
+                    current.input_redirect = Some(path);
+                } else {
+                    current.output_redirect = Some(OutputRedirect {
+                        path,
+                        append: operator == Operator::RedirectAppend,
+                    });
+                }
+            }
+        }
+    }
+
+    if current.words.is_empty() {
+        if !stages.is_empty() {
+            return Err(format!("syntax error near '{}'", Operator::Pipe));
+        }
+        if current.output_redirect.is_none() && current.input_redirect.is_none() {
+            return Ok(stages);
+        }
+        return Err(String::from("missing command before redirection"));
+    }
+
+    stages.push(current);
+    Ok(stages)
+}
+
+/// Replaces `$NAME`, `${NAME}` and `$?` in a word with their values from `variable`.
+/// Unset variables expand to an empty string and `\$` yields a literal `$`.
+pub fn expand_variables(
+    word: &str,
+    last_exit_code: i32,
+    variable: &dyn Fn(&str) -> Option<String>,
+) -> String {
+    let mut result = String::with_capacity(word.len());
+    let mut chars = word.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek() == Some(&'$') => {
+                chars.next();
+                result.push('$');
+            }
+            '$' => match chars.peek() {
+                Some('?') => {
+                    chars.next();
+                    result.push_str(&last_exit_code.to_string());
+                }
+                Some('{') => {
+                    chars.next();
+                    let name: String = chars.by_ref().take_while(|&c| c != '}').collect();
+                    result.push_str(&variable(&name).unwrap_or_default());
+                }
+                Some(&next) if is_variable_char(next) => {
+                    let mut name = String::new();
+                    while let Some(&next) = chars.peek() {
+                        if !is_variable_char(next) {
+                            break;
+                        }
+                        name.push(next);
+                        chars.next();
+                    }
+                    result.push_str(&variable(&name).unwrap_or_default());
+                }
+                _ => result.push('$'),
+            },
+            _ => result.push(c),
+        }
+    }
+
+    result
+}
+
+/// Whether `name` can be referenced as `$name`
+pub fn is_variable_name(name: &str) -> bool {
+    name.chars().next().is_some_and(|c| !c.is_ascii_digit()) && name.chars().all(is_variable_char)
+}
+
+fn is_variable_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use tempfile::TempDir;
+
+    use super::*;
+
+    fn no_variables(_name: &str) -> Option<String> {
+        None
+    }
+
+    fn words(input: &str) -> Vec<String> {
+        words_in(input, Path::new("."))
+    }
+
+    fn words_in(input: &str, current_path: &Path) -> Vec<String> {
+        tokenize(input, 0, &no_variables, current_path)
+            .unwrap()
+            .into_iter()
+            .map(|token| match token {
+                Token::Word(word) => word,
+                Token::Operator(operator) => operator.to_string(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn quotes_and_escapes_keep_spaces() {
+        assert_eq!(words(r#"cd "a b""#), ["cd", "a b"]);
+        assert_eq!(words("echo 'hello world'"), ["echo", "hello world"]);
+        assert_eq!(words(r"touch a\ b.txt"), ["touch", "a b.txt"]);
+        assert_eq!(
+            words(r#"echo "it's" 'say "hi"' mi'x'"ed""#),
+            ["echo", "it's", "say \"hi\"", "mixed"]
+        );
+    }
+
+    #[test]
+    fn unterminated_quotes_are_errors() {
+        assert!(tokenize("echo 'open", 0, &no_variables, Path::new(".")).is_err());
+        assert!(tokenize("echo \"open", 0, &no_variables, Path::new(".")).is_err());
+    }
+
+    #[test]
+    fn variables_are_looked_up_outside_of_single_quotes() {
+        let variable = |name: &str| (name == "NAME").then(|| String::from("mbash"));
+        let words: Vec<Token> = tokenize(
+            r#"echo $NAME "${NAME}!" '$NAME' \$NAME $MISSING $?"#,
+            3,
+            &variable,
+            Path::new("."),
+        )
+        .unwrap();
+        assert_eq!(
+            words,
+            ["echo", "mbash", "mbash!", "$NAME", "$NAME", "", "3"]
+                .map(|word| Token::Word(word.to_string()))
+        );
+    }
+
+    #[test]
+    fn pipelines_split_on_pipes() {
+        let tokens = tokenize("ls -a | grep txt | wc", 0, &no_variables, Path::new(".")).unwrap();
+        let stages = parse_pipeline(tokens).unwrap();
+
+        let words: Vec<Vec<String>> = stages.into_iter().map(|stage| stage.words).collect();
+        assert_eq!(words, [vec!["ls", "-a"], vec!["grep", "txt"], vec!["wc"]]);
+    }
+
+    #[test]
+    fn pipelines_need_a_command_on_each_side() {
+        for input in ["| cat", "ls |", "ls | | cat"] {
+            let tokens = tokenize(input, 0, &no_variables, Path::new(".")).unwrap();
+            assert!(parse_pipeline(tokens).is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn redirects_are_taken_out_of_the_words() {
+        let tokens = tokenize("ls -l >> files.txt", 0, &no_variables, Path::new(".")).unwrap();
+        let stages = parse_pipeline(tokens).unwrap();
+
+        assert_eq!(stages[0].words, ["ls", "-l"]);
+        let redirect = stages[0].output_redirect.as_ref().unwrap();
+        assert_eq!(redirect.path, "files.txt");
+        assert!(redirect.append);
+
+        let tokens = tokenize("ls >", 0, &no_variables, Path::new(".")).unwrap();
+        assert!(parse_pipeline(tokens).is_err());
+    }
+
+    #[test]
+    fn operators_are_found_outside_of_quotes() {
+        for input in [
+            "ls | wc",
+            "a && b",
+            "a; b",
+            "ls > out",
+            "sort < in",
+            "sleep 1 &",
+        ] {
+            assert!(has_unquoted_operator(input), "{}", input);
+        }
+        for input in ["ls -l", "echo 'a | b'", "echo \"a; b\"", r"echo a\>b"] {
+            assert!(!has_unquoted_operator(input), "{}", input);
+        }
+    }
+
+    #[test]
+    fn command_lists_split_outside_of_quotes() {
+        let segments = split_command_list("a; b && 'c; d' || e &").unwrap();
+
+        let parts: Vec<(Connector, &str, bool)> = segments
+            .iter()
+            .map(|segment| (segment.connector, segment.text.trim(), segment.background))
+            .collect();
+        assert_eq!(
+            parts,
+            [
+                (Connector::Always, "a", false),
+                (Connector::Always, "b", false),
+                (Connector::IfSuccess, "'c; d'", false),
+                (Connector::IfFailure, "e", true),
+            ]
+        );
+    }
+
+    #[test]
+    fn trailing_backslashes_continue_the_line() {
+        assert_eq!(continuation(r"echo one \"), Some(Continuation::Backslash));
+        assert_eq!(continuation(r"echo one \\"), None);
+        assert_eq!(continuation("echo one"), None);
+
+        let mut command_line = String::from(r"echo one \");
+        continue_line(&mut command_line, Continuation::Backslash, "two");
+        assert_eq!(command_line, "echo one two");
+    }
+
+    #[test]
+    fn open_quotes_continue_the_line() {
+        assert_eq!(continuation("echo 'one"), Some(Continuation::Quote));
+        assert_eq!(continuation(r#"echo "it's"#), Some(Continuation::Quote));
+        assert_eq!(continuation(r"echo 'a\"), Some(Continuation::Quote));
+
+        let mut command_line = String::from("echo 'one");
+        continue_line(&mut command_line, Continuation::Quote, "two'");
+        assert_eq!(command_line, "echo 'one\ntwo'");
+        assert_eq!(continuation(&command_line), None);
+        assert_eq!(words(&command_line), ["echo", "one\ntwo"]);
+    }
+
+    #[test]
+    fn braces_expand_comma_lists() {
+        assert_eq!(
+            expand_braces("touch file{1,2,3}.txt").unwrap(),
+            "touch file1.txt file2.txt file3.txt"
+        );
+        assert_eq!(expand_braces("mkdir dir{a,b}").unwrap(), "mkdir dira dirb");
+    }
+
+    #[test]
+    fn braces_expand_ranges() {
+        assert_eq!(expand_braces("echo {1..3}").unwrap(), "echo 1 2 3");
+        assert_eq!(expand_braces("echo {3..1}").unwrap(), "echo 3 2 1");
+        assert_eq!(expand_braces("echo {a..c}").unwrap(), "echo a b c");
+    }
+
+    #[test]
+    fn braces_nest() {
+        assert_eq!(
+            expand_braces("echo {a,b{1,2}}x").unwrap(),
+            "echo ax b1x b2x"
+        );
+    }
+
+    #[test]
+    fn quoted_escaped_and_single_braces_stay_literal() {
+        assert_eq!(expand_braces(r"echo \{a,b\}").unwrap(), r"echo \{a,b\}");
+        assert_eq!(expand_braces("echo '{a,b}'").unwrap(), "echo '{a,b}'");
+        assert_eq!(
+            expand_braces("echo {a} ${HOME}").unwrap(),
+            "echo {a} ${HOME}"
+        );
+    }
+
+    #[test]
+    fn huge_brace_expansions_are_errors() {
+        assert!(expand_braces("echo {1..3000000000}").is_err());
+        assert!(expand_braces("echo {1..100}{1..100}{1..100}").is_err());
+    }
+
+    /// A directory holding `main.rs`, `lib.rs`, `a1.txt`, `a22.txt` and `.hidden.rs`
+    fn glob_directory() -> TempDir {
+        let directory = TempDir::new().unwrap();
+        for name in ["main.rs", "lib.rs", "a1.txt", "a22.txt", ".hidden.rs"] {
+            fs::write(directory.path().join(name), "").unwrap();
+        }
+        directory
+    }
+
+    #[test]
+    fn stars_expand_to_sorted_matches() {
+        let directory = glob_directory();
+
+        assert_eq!(
+            words_in("cat *.rs", directory.path()),
+            ["cat", "lib.rs", "main.rs"]
+        );
+    }
+
+    #[test]
+    fn question_marks_match_one_character() {
+        let directory = glob_directory();
+
+        assert_eq!(words_in("cat a?.txt", directory.path()), ["cat", "a1.txt"]);
+    }
+
+    #[test]
+    fn unmatched_and_quoted_globs_stay_literal() {
+        let directory = glob_directory();
+
+        assert_eq!(words_in("cat *.md", directory.path()), ["cat", "*.md"]);
+        assert_eq!(words_in("cat '*.rs'", directory.path()), ["cat", "*.rs"]);
+        assert_eq!(words_in(r"cat \*.rs", directory.path()), ["cat", "*.rs"]);
+    }
+}
@@ -0,0 +1,154 @@
+use std::{
+    fs,
+    path::Path,
+    process::{Command, Output},
+};
+use tempfile::TempDir;
+
+/// Runs the mbash binary in `directory`, with `HOME` pointing there so no `.mbashrc` is picked up
+fn mbash(directory: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_mbash"))
+        .args(args)
+        .current_dir(directory)
+        .env("HOME", directory)
+        .env("MBASH_LOG_LEVEL", "ERROR")
+        .output()
+        .unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+#[test]
+fn scripts_run_line_by_line() {
+    let directory = TempDir::new().unwrap();
+    let path = directory.path().canonicalize().unwrap();
+    fs::write(
+        path.join("setup.mbash"),
+        "# make a directory and enter it\nmkdir sub\n\ncd sub\npwd\n",
+    )
+    .unwrap();
+
+    let output = mbash(&path, &["setup.mbash"]);
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), format!("{}\n", path.join("sub").display()));
+}
+
+#[test]
+fn missing_scripts_fail() {
+    let directory = TempDir::new().unwrap();
+
+    let output = mbash(directory.path(), &["missing.mbash"]);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn dash_c_runs_one_command() {
+    let directory = TempDir::new().unwrap();
+    let path = directory.path().canonicalize().unwrap();
+
+    let output = mbash(&path, &["-c", "pwd"]);
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), format!("{}\n", path.display()));
+}
+
+#[test]
+fn dash_c_exits_with_the_command_status() {
+    let directory = TempDir::new().unwrap();
+
+    let output = mbash(directory.path(), &["-c", "cat missing.txt"]);
+    assert_eq!(output.status.code(), Some(1));
+
+    let output = mbash(directory.path(), &["-c"]);
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn log_file_collects_the_logs() {
+    let directory = TempDir::new().unwrap();
+    let log_file = directory.path().join("mbash.log");
+
+    let output = mbash(
+        directory.path(),
+        &[
+            "--log-file",
+            log_file.to_str().unwrap(),
+            "-c",
+            "cat missing.txt",
+        ],
+    );
+    assert_eq!(stdout(&output), "");
+    let logs = fs::read_to_string(&log_file).unwrap();
+    assert!(logs.contains("[ERROR] Failed to open"));
+}
+
+#[test]
+fn unopenable_log_file_falls_back_to_stdout() {
+    let directory = TempDir::new().unwrap();
+
+    let output = mbash(
+        directory.path(),
+        &["--log-file", "missing/mbash.log", "-c", "cat missing.txt"],
+    );
+    assert!(String::from_utf8_lossy(&output.stderr).contains("logging to stdout instead"));
+    assert!(stdout(&output).contains("[ERROR] Failed to open"));
+}
+
+#[test]
+fn rc_file_runs_at_startup() {
+    let directory = TempDir::new().unwrap();
+    fs::write(
+        directory.path().join(".mbashrc"),
+        "# greetings\nalias greet='echo hello'\n",
+    )
+    .unwrap();
+
+    let output = mbash(directory.path(), &["-c", "greet"]);
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "hello\n");
+}
+
+#[test]
+fn dash_c_fails_with_a_failing_command() {
+    let directory = TempDir::new().unwrap();
+
+    let output = mbash(directory.path(), &["-c", "cd missing"]);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn failed_setup_exits_with_failure() {
+    let directory = TempDir::new().unwrap();
+    fs::write(directory.path().join(".mtracking"), b"\xff\xfe\n").unwrap();
+
+    let output = mbash(directory.path(), &["-c", "echo hello"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(stdout(&output), "");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("failed to start"));
+}
+
+#[test]
+fn tracking_changes_are_saved_on_exit() {
+    let directory = TempDir::new().unwrap();
+
+    let output = mbash(directory.path(), &["-c", "init; touch a.txt; add a.txt"]);
+    assert!(output.status.success());
+    assert_eq!(
+        fs::read_to_string(directory.path().join(".mtracking")).unwrap(),
+        "a.txt\n"
+    );
+}
+
+#[test]
+fn version_prints_the_package_version() {
+    let directory = TempDir::new().unwrap();
+    let expected = format!("mbash {}\n", env!("CARGO_PKG_VERSION"));
+
+    let output = mbash(directory.path(), &["--version"]);
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), expected);
+
+    let output = mbash(directory.path(), &["-c", "version"]);
+    assert_eq!(stdout(&output), expected);
+}
@@ -0,0 +1,43 @@
+use logger::{LogLevel, stdout_logger::StdoutLogger};
+use mbash::{Mbash, MbashError, OutputSink};
+use tempfile::TempDir;
+
+fn shell() -> Mbash {
+    let mut mbash = Mbash::new(Box::new(StdoutLogger::new(LogLevel::ERROR)));
+    mbash.set_output(OutputSink::Buffer(Vec::new()));
+    mbash
+}
+
+fn take_output(mbash: &mut Mbash) -> String {
+    let output = mbash.set_output(OutputSink::Buffer(Vec::new()));
+    String::from_utf8(output.into_buffer()).unwrap()
+}
+
+#[test]
+fn execute_line_drives_the_shell() {
+    let directory = TempDir::new().unwrap();
+    let path = directory.path().canonicalize().unwrap();
+    let mut mbash = shell();
+
+    mbash.execute_line(&format!("cd '{}'", path.display()));
+    assert_eq!(mbash.last_exit_code(), 0);
+    take_output(&mut mbash);
+
+    mbash.execute_line("pwd");
+    assert_eq!(take_output(&mut mbash), format!("{}\n", path.display()));
+    assert_eq!(mbash.last_exit_code(), 0);
+}
+
+#[test]
+fn failing_commands_surface_as_command_failed() {
+    let mut mbash = shell();
+
+    assert!(mbash.try_execute_line("echo fine").is_ok());
+    match mbash.try_execute_line("cat missing.txt") {
+        Err(MbashError::CommandFailed { command, status }) => {
+            assert_eq!(command, "cat missing.txt");
+            assert_eq!(status, 1);
+        }
+        other => panic!("expected a command failure, got {:?}", other),
+    }
+}